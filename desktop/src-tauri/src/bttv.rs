@@ -0,0 +1,271 @@
+use futures::future::{BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+
+use crate::provider::{
+    get_json, ChannelInput, DownloadCandidate, EmoteFile, EmoteOwner, EmoteProvider, EmoteSource, Platform,
+    ProviderEmote, ResolvedChannel,
+};
+use crate::http::HttpClient;
+use crate::CommandError;
+
+pub const BTTV_API_BASE: &str = "https://api.betterttv.net/3";
+pub const BTTV_CDN_BASE: &str = "https://cdn.betterttv.net";
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BttvEmoteUser {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+    pub provider_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BttvEmote {
+    pub id: String,
+    pub code: String,
+    pub image_type: String,
+    #[serde(default)]
+    pub animated: bool,
+    // Channel emotes carry the uploader's id, shared emotes the full user
+    pub user_id: Option<String>,
+    pub user: Option<BttvEmoteUser>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BttvUserResponse {
+    pub id: String,
+    #[serde(default)]
+    pub bots: Vec<String>,
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub channel_emotes: Vec<BttvEmote>,
+    #[serde(default)]
+    pub shared_emotes: Vec<BttvEmote>,
+}
+
+// BetterTTV channel and shared emotes. The base URLs are configurable so the
+// sync can be pointed at a local stand-in server.
+#[derive(Debug, Clone)]
+pub struct BttvProvider {
+    api_base: String,
+    cdn_base: String,
+}
+
+impl Default for BttvProvider {
+    fn default() -> Self {
+        Self::with_base_urls(BTTV_API_BASE, BTTV_CDN_BASE)
+    }
+}
+
+impl BttvProvider {
+    pub fn with_base_urls(api_base: &str, cdn_base: &str) -> Self {
+        Self {
+            api_base: api_base.trim_end_matches('/').to_string(),
+            cdn_base: cdn_base.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn user_url(&self, twitch_id: &str) -> String {
+        format!("{}/cached/users/twitch/{}", self.api_base, twitch_id)
+    }
+
+    pub fn emote_url(&self, emote: &BttvEmote, scale: u32) -> String {
+        format!("{}/emote/{}/{}x.{}", self.cdn_base, emote.id, scale, emote.image_type)
+    }
+
+    fn normalize(&self, emote: &BttvEmote) -> ProviderEmote {
+        let owner = emote.user.as_ref().map(|user| EmoteOwner {
+            id: user.id.clone(),
+            username: user.name.clone(),
+            display_name: user.display_name.clone(),
+        });
+
+        ProviderEmote {
+            id: emote.id.clone(),
+            code: emote.code.clone(),
+            original_name: None,
+            animated: emote.animated,
+            files: (1..=3)
+                .map(|scale| EmoteFile {
+                    name: format!("{}x.{}", scale, emote.image_type),
+                    url: self.emote_url(emote, scale),
+                    format: emote.image_type.to_lowercase(),
+                    scale,
+                    width: None,
+                    height: None,
+                    static_file: None,
+                })
+                .collect(),
+            owner,
+            tags: Vec::new(),
+            added_at: None,
+        }
+    }
+}
+
+impl EmoteProvider for BttvProvider {
+    fn name(&self) -> &'static str {
+        "BTTV"
+    }
+
+    fn folder(&self) -> &'static str {
+        "bttv_emotes"
+    }
+
+    fn resolve_channel<'a>(
+        &'a self,
+        _client: &'a HttpClient,
+        input: &'a ChannelInput,
+    ) -> BoxFuture<'a, Result<Option<ResolvedChannel>, CommandError>> {
+        // BTTV is keyed by Twitch user id, so there is nothing to look up.
+        // Channels on other platforms have no BTTV emotes.
+        let channel = (input.platform == Platform::Twitch).then(|| ResolvedChannel {
+            source: EmoteSource::Channel(input.id.clone()),
+            dir_name: input.dir_name(),
+            label: input.to_string(),
+            namespace: input.namespace(),
+        });
+        async move { Ok(channel) }.boxed()
+    }
+
+    // Returns the channel and shared emotes of a Twitch user. Users without a
+    // BetterTTV account (the API answers 404) have no emotes.
+    fn list_emotes<'a>(
+        &'a self,
+        client: &'a HttpClient,
+        channel: &'a ResolvedChannel,
+    ) -> BoxFuture<'a, Result<Vec<ProviderEmote>, CommandError>> {
+        async move {
+            let EmoteSource::Channel(twitch_id) = &channel.source else {
+                return Ok(Vec::new());
+            };
+            let Some(user) = get_json::<BttvUserResponse>(client, &self.user_url(twitch_id)).await? else {
+                return Ok(Vec::new());
+            };
+            Ok(user
+                .channel_emotes
+                .iter()
+                .chain(user.shared_emotes.iter())
+                .map(|emote| self.normalize(emote))
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn download_candidates(&self, emote: &ProviderEmote) -> Vec<DownloadCandidate> {
+        // Only the largest scale; smaller ones are extra sizes
+        let scale = emote.files.iter().map(|file| file.scale).max().unwrap_or_default();
+        emote
+            .files
+            .iter()
+            .filter(|file| file.scale == scale)
+            .map(|file| DownloadCandidate {
+                url: file.url.clone(),
+                extension: file.format.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::{save_emote_file, HostLimiter};
+    use crate::http::RetryPolicy;
+    use crate::paths::emote_file_name;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const USER_RESPONSE: &str = r#"{
+        "id": "bttv-user",
+        "channelEmotes": [
+            {"id": "e1", "code": "Clap", "imageType": "png", "animated": false, "userId": "bttv-user"}
+        ],
+        "sharedEmotes": [
+            {"id": "e2", "code": "catJAM", "imageType": "gif", "animated": true,
+             "user": {"id": "u2", "name": "someone", "displayName": "Someone"}}
+        ]
+    }"#;
+
+    // Answers every request on a local port: the user endpoint with
+    // `USER_RESPONSE`, emote files with their own path as the body, anything
+    // else with a 404
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0; 4096];
+                let read = stream.read(&mut request).await.unwrap_or_default();
+                let request = String::from_utf8_lossy(&request[..read]);
+                let path = request.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let (status, body) = if path == "/api/cached/users/twitch/12345" {
+                    ("200 OK", USER_RESPONSE.to_string())
+                } else if path.starts_with("/cdn/emote/") {
+                    ("200 OK", path)
+                } else {
+                    ("404 Not Found", String::new())
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn syncs_against_a_local_server() {
+        let base = serve().await;
+        let provider = BttvProvider::with_base_urls(&format!("{}/api/", base), &format!("{}/cdn", base));
+        let client = HttpClient::new(reqwest::Client::new(), RetryPolicy::default());
+        let input = ChannelInput::parse("12345").unwrap();
+
+        let channel = provider.resolve_channel(&client, &input).await.unwrap().unwrap();
+        let emotes = provider.list_emotes(&client, &channel).await.unwrap();
+        let codes: Vec<&str> = emotes.iter().map(|emote| emote.code.as_str()).collect();
+        assert_eq!(codes, ["Clap", "catJAM"]);
+        assert_eq!(emotes[1].owner.as_ref().map(|owner| owner.username.as_str()), Some("someone"));
+
+        let dir = std::env::temp_dir().join(format!("mojify-bttv-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let limiter = HostLimiter::new(2);
+        let mut stored = Vec::new();
+        for emote in &emotes {
+            let candidate = &provider.download_candidates(emote)[0];
+            let filename = emote_file_name(&emote.code, &candidate.extension);
+            save_emote_file(&client, &limiter, &candidate.url, &dir.join(&filename)).await.unwrap();
+            let body = std::fs::read_to_string(dir.join(&filename)).unwrap();
+            stored.push((filename, body));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            stored,
+            [
+                ("Clap.png".to_string(), "/cdn/emote/e1/3x.png".to_string()),
+                ("catJAM.gif".to_string(), "/cdn/emote/e2/3x.gif".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_users_have_no_emotes() {
+        let base = serve().await;
+        let provider = BttvProvider::with_base_urls(&format!("{}/api", base), &format!("{}/cdn", base));
+        let client = HttpClient::new(reqwest::Client::new(), RetryPolicy::default());
+        let channel = provider
+            .resolve_channel(&client, &ChannelInput::parse("999").unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(provider.list_emotes(&client, &channel).await.unwrap().is_empty());
+    }
+}
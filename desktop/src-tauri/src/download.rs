@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::http::HttpClient;
+use crate::report::{Failure, FailureReason};
+use crate::storage::write_atomic;
+
+// Caps the number of requests in flight to any single host, so parallel
+// channels can't hammer one CDN while another sits idle.
+pub struct HostLimiter {
+    per_host: usize,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    pub fn new(per_host: usize) -> Self {
+        Self {
+            per_host: per_host.max(1),
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn acquire(&self, url: &str) -> OwnedSemaphorePermit {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let semaphore = {
+            let mut semaphores = self.semaphores.lock().unwrap_or_else(|e| e.into_inner());
+            semaphores
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.per_host)))
+                .clone()
+        };
+        // The semaphore is never closed, so acquiring can't fail
+        semaphore.acquire_owned().await.expect("host semaphore closed")
+    }
+}
+
+// Fetches `url` while holding a permit for its host. Transient failures are
+// retried by the client before this gives up.
+pub async fn fetch_file(client: &HttpClient, limiter: &HostLimiter, url: &str) -> Result<bytes::Bytes, Failure> {
+    let _permit = limiter.acquire(url).await;
+    let response = client
+        .get(url)
+        .await
+        .map_err(|e| Failure::new(FailureReason::Request, format!("HTTP request error: {:#?}", e)))?;
+    if !response.status.is_success() {
+        return Err(Failure::new(
+            FailureReason::HttpStatus { status: response.status.as_u16() },
+            format!("HTTP {} response", response.status),
+        ));
+    }
+    Ok(response.body)
+}
+
+// Downloads `url` into `output_path`, returning the number of bytes written
+pub async fn save_emote_file(
+    client: &HttpClient,
+    limiter: &HostLimiter,
+    url: &str,
+    output_path: &Path,
+) -> Result<usize, Failure> {
+    let bytes = fetch_file(client, limiter, url).await?;
+    // A half-written file would pass for a complete one on the next sync
+    write_atomic(output_path, &bytes)
+        .map_err(|e| Failure::new(FailureReason::Io, format!("Failed to write file: {}", e)))?;
+    Ok(bytes.len())
+}
+
+// Whether `save_first_frame` can decode files with this extension. The image
+// crate is built without AVIF support.
+pub fn can_decode(extension: &str) -> bool {
+    image::ImageFormat::from_extension(extension).is_some_and(|format| format.reading_enabled())
+}
+
+// Writes the first frame of the image in `input` to `output_path` as a PNG,
+// for animated emotes whose provider serves no static variant. Returns the
+// number of bytes written.
+pub fn save_first_frame(input: &[u8], output_path: &Path) -> Result<usize, Failure> {
+    let frame = image::load_from_memory(input)
+        .map_err(|e| Failure::new(FailureReason::Decode, format!("Failed to decode the emote: {}", e)))?;
+    let mut bytes = Vec::new();
+    frame
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+        .map_err(|e| Failure::new(FailureReason::Decode, format!("Failed to encode first frame: {}", e)))?;
+    write_atomic(output_path, &bytes)
+        .map_err(|e| Failure::new(FailureReason::Io, format!("Failed to write file: {}", e)))?;
+    Ok(bytes.len())
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{Emitter, Window, Wry};
+
+use crate::jobs::JobId;
+use crate::report::Failure;
+
+pub const CHANNEL_STARTED_EVENT: &str = "sync://channel-started";
+pub const EMOTE_PROGRESS_EVENT: &str = "sync://emote-progress";
+pub const CHANNEL_FINISHED_EVENT: &str = "sync://channel-finished";
+
+// Whether the free-text `download://log` stream is emitted next to the typed
+// events. Managed by Tauri and kept in step with `Settings::text_log`.
+pub struct TextLog(AtomicBool);
+
+impl Default for TextLog {
+    fn default() -> Self {
+        Self(AtomicBool::new(true))
+    }
+}
+
+impl TextLog {
+    pub fn enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmoteStatus {
+    Downloaded,
+    Skipped,
+    Renamed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelStatus {
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelStarted {
+    pub job_id: JobId,
+    pub provider: String,
+    pub channel: String,
+    pub total: usize,
+}
+
+// Sent once per emote, with running counts for its channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmoteProgress {
+    pub job_id: JobId,
+    pub provider: String,
+    pub channel: String,
+    pub emote_id: String,
+    pub emote_code: String,
+    pub status: EmoteStatus,
+    pub bytes: u64,
+    pub error: Option<Failure>,
+    pub completed: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelFinished {
+    pub job_id: JobId,
+    pub provider: String,
+    pub channel: String,
+    pub status: ChannelStatus,
+    pub processed: usize,
+    pub downloaded: usize,
+    pub skipped: usize,
+    pub renamed: usize,
+    pub failed: usize,
+    pub bytes: u64,
+    pub pruned: usize,
+    pub error: Option<Failure>,
+}
+
+pub fn emit_event<T: Serialize + Clone>(window: &Window<Wry>, event: &str, payload: T) {
+    if let Err(e) = window.emit(event, payload) {
+        eprintln!("Failed to emit {} event: {}", event, e);
+    }
+}
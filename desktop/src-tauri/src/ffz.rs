@@ -0,0 +1,229 @@
+use futures::future::{BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::provider::{
+    absolute_url, get_json, ChannelInput, DownloadCandidate, EmoteFile, EmoteOwner, EmoteProvider, EmoteSource,
+    Platform, ProviderEmote, ResolvedChannel, StaticFile,
+};
+use crate::http::HttpClient;
+use crate::CommandError;
+
+pub const FFZ_API_BASE: &str = "https://api.frankerfacez.com/v1";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FfzEmoteOwner {
+    #[serde(rename = "_id")]
+    pub id: u64,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FfzEmote {
+    pub id: u64,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub public: bool,
+    pub owner: Option<FfzEmoteOwner>,
+    // Scale ("1", "2", "4") -> URL
+    pub urls: HashMap<String, String>,
+    // Only present for animated emotes, same shape as `urls`
+    pub animated: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FfzEmoteSet {
+    pub id: u64,
+    pub title: String,
+    #[serde(default)]
+    pub emoticons: Vec<FfzEmote>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FfzRoom {
+    pub twitch_id: Option<u64>,
+    pub id: String,
+    pub display_name: Option<String>,
+    pub set: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FfzRoomResponse {
+    pub room: FfzRoom,
+    pub sets: HashMap<String, FfzEmoteSet>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FfzGlobalResponse {
+    pub default_sets: Vec<u64>,
+    pub sets: HashMap<String, FfzEmoteSet>,
+}
+
+// FrankerFaceZ room sets of a Twitch channel plus the FFZ global sets
+#[derive(Debug, Clone)]
+pub struct FfzProvider {
+    api_base: String,
+}
+
+impl Default for FfzProvider {
+    fn default() -> Self {
+        Self::with_base_url(FFZ_API_BASE)
+    }
+}
+
+impl FfzProvider {
+    pub fn with_base_url(api_base: &str) -> Self {
+        Self {
+            api_base: api_base.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn room_url(&self, twitch_id: &str) -> String {
+        format!("{}/room/id/{}", self.api_base, twitch_id)
+    }
+
+    pub fn global_url(&self) -> String {
+        format!("{}/set/global", self.api_base)
+    }
+
+    fn normalize(&self, emote: &FfzEmote) -> ProviderEmote {
+        // FFZ serves the static frames under `urls` and the animated ones
+        // (as WebP) under `animated`, each keyed by scale
+        let static_files = emote.urls.iter().map(|urls| (urls, "png"));
+        let animated_files = emote.animated.iter().flatten().map(|urls| (urls, "webp"));
+        let files = static_files
+            .chain(animated_files)
+            .filter_map(|((scale, url), format)| {
+                let scale: u32 = scale.parse().ok()?;
+                Some(EmoteFile {
+                    name: format!("{}x.{}", scale, format),
+                    url: absolute_url(url),
+                    format: format.to_string(),
+                    scale,
+                    width: Some(emote.width * scale),
+                    height: Some(emote.height * scale),
+                    // The static PNG at the same scale
+                    static_file: match format {
+                        "webp" => emote.urls.get(&scale.to_string()).map(|url| StaticFile {
+                            url: absolute_url(url),
+                            format: "png".to_string(),
+                        }),
+                        _ => None,
+                    },
+                })
+            })
+            .collect();
+        let owner = emote.owner.as_ref().map(|owner| EmoteOwner {
+            id: owner.id.to_string(),
+            username: owner.name.clone(),
+            display_name: owner.display_name.clone(),
+        });
+
+        ProviderEmote {
+            id: emote.id.to_string(),
+            code: emote.name.clone(),
+            original_name: None,
+            animated: emote.animated.as_ref().is_some_and(|animated| !animated.is_empty()),
+            files,
+            owner,
+            tags: Vec::new(),
+            added_at: None,
+        }
+    }
+}
+
+impl EmoteProvider for FfzProvider {
+    fn name(&self) -> &'static str {
+        "FFZ"
+    }
+
+    fn folder(&self) -> &'static str {
+        "ffz_emotes"
+    }
+
+    fn resolve_channel<'a>(
+        &'a self,
+        _client: &'a HttpClient,
+        input: &'a ChannelInput,
+    ) -> BoxFuture<'a, Result<Option<ResolvedChannel>, CommandError>> {
+        // Rooms are looked up by Twitch user id directly. FFZ only exists on
+        // Twitch, so other platforms have no rooms.
+        let channel = (input.platform == Platform::Twitch).then(|| ResolvedChannel {
+            source: EmoteSource::Channel(input.id.clone()),
+            dir_name: input.dir_name(),
+            label: input.to_string(),
+            namespace: input.namespace(),
+        });
+        async move { Ok(channel) }.boxed()
+    }
+
+    // Returns the emotes of every set attached to the channel's room, or of
+    // the default (global) sets. Channels without a room have no emotes.
+    fn list_emotes<'a>(
+        &'a self,
+        client: &'a HttpClient,
+        channel: &'a ResolvedChannel,
+    ) -> BoxFuture<'a, Result<Vec<ProviderEmote>, CommandError>> {
+        async move {
+            let sets: Vec<FfzEmoteSet> = match &channel.source {
+                EmoteSource::Channel(twitch_id) => {
+                    match get_json::<FfzRoomResponse>(client, &self.room_url(twitch_id)).await? {
+                        // By set id, so the listing (and which emote keeps a
+                        // name two sets share) is the same on every sync
+                        Some(room) => {
+                            let mut sets: Vec<FfzEmoteSet> = room.sets.into_values().collect();
+                            sets.sort_by_key(|set| set.id);
+                            sets
+                        }
+                        None => Vec::new(),
+                    }
+                }
+                EmoteSource::Global => {
+                    match get_json::<FfzGlobalResponse>(client, &self.global_url()).await? {
+                        Some(mut global) => global
+                            .default_sets
+                            .iter()
+                            .filter_map(|set_id| global.sets.remove(&set_id.to_string()))
+                            .collect(),
+                        None => Vec::new(),
+                    }
+                }
+                EmoteSource::EmoteSet(_) => Vec::new(),
+            };
+            Ok(sets
+                .iter()
+                .flat_map(|set| set.emoticons.iter())
+                .map(|emote| self.normalize(emote))
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn download_candidates(&self, emote: &ProviderEmote) -> Vec<DownloadCandidate> {
+        // Largest available scale, preferring the animated variant
+        let format = if emote.animated { "webp" } else { "png" };
+        emote
+            .files
+            .iter()
+            .filter(|file| file.format == format)
+            .max_by_key(|file| file.scale)
+            .map(|file| DownloadCandidate {
+                url: file.url.clone(),
+                extension: file.format.clone(),
+            })
+            .into_iter()
+            .collect()
+    }
+
+    fn global_channel(&self) -> Option<ResolvedChannel> {
+        Some(ResolvedChannel {
+            source: EmoteSource::Global,
+            dir_name: "global".to_string(),
+            label: "global".to_string(),
+            namespace: "ffz_global".to_string(),
+        })
+    }
+}
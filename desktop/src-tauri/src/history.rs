@@ -0,0 +1,41 @@
+use serde_json::Value;
+use std::path::Path;
+
+use crate::report::SyncReport;
+use crate::storage::{read_json_with_recovery, write_json_with_backup, JsonRead};
+use crate::CommandError;
+
+const SYNC_HISTORY_FILE: &str = "sync_history.json";
+
+// Oldest reports are dropped past this many
+const MAX_HISTORY_ENTRIES: usize = 50;
+
+// The stored reports as raw JSON, so entries this version can't read are
+// still written back untouched. A damaged file with no usable backup is
+// treated as empty rather than failing the sync that appends to it.
+fn read_entries(mojify_data_dir: &Path) -> Vec<Value> {
+    match read_json_with_recovery(&mojify_data_dir.join(SYNC_HISTORY_FILE)) {
+        Ok(JsonRead::Ok(content) | JsonRead::Restored(content)) => serde_json::from_str(&content).unwrap_or_default(),
+        Ok(JsonRead::Missing | JsonRead::Corrupt) | Err(_) => Vec::new(),
+    }
+}
+
+// Past sync reports, oldest first. Reports that can't be read are left out.
+pub fn load_history(mojify_data_dir: &Path) -> Vec<SyncReport> {
+    read_entries(mojify_data_dir)
+        .into_iter()
+        .filter_map(|entry| serde_json::from_value(entry).ok())
+        .collect()
+}
+
+pub fn append_history(mojify_data_dir: &Path, report: &SyncReport) -> Result<(), CommandError> {
+    let mut history = read_entries(mojify_data_dir);
+    history.push(serde_json::to_value(report)?);
+    let excess = history.len().saturating_sub(MAX_HISTORY_ENTRIES);
+    history.drain(..excess);
+    write_json_with_backup(
+        &mojify_data_dir.join(SYNC_HISTORY_FILE),
+        serde_json::to_string_pretty(&history)?.as_bytes(),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+// Longest `Retry-After` we are willing to sit through for a single request
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    // Extra attempts after the first one
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Capped exponential backoff with "equal jitter": half of the delay is
+    // fixed, the other half random, so parallel retries spread out without
+    // ever collapsing to zero.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self.base_delay_ms.saturating_mul(1u64 << retry.min(20));
+        let capped = exponential.min(self.max_delay_ms);
+        let half = capped / 2;
+        Duration::from_millis(half + rand::thread_rng().gen_range(0..=half))
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// `Retry-After` is either a number of seconds or an HTTP date
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let status = response.status();
+    if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let delay = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .unwrap_or_default(),
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+// A fully read response
+pub struct Fetched {
+    pub status: StatusCode,
+    pub body: bytes::Bytes,
+}
+
+// reqwest client that retries transient failures (request errors, 5xx and
+// 429) according to a `RetryPolicy`, and counts how often it had to.
+pub struct HttpClient {
+    client: reqwest::Client,
+    policy: RetryPolicy,
+    retries: AtomicUsize,
+}
+
+impl HttpClient {
+    pub fn new(client: reqwest::Client, policy: RetryPolicy) -> Self {
+        Self {
+            client,
+            policy,
+            retries: AtomicUsize::new(0),
+        }
+    }
+
+    // Retries performed so far, across every request made with this client
+    pub fn retries(&self) -> usize {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Fetched, reqwest::Error> {
+        self.fetch(|client| client.get(url)).await
+    }
+
+    // Sends the request built by `request` and reads its body, retrying the
+    // whole exchange on transient failures. The last response or error is
+    // returned once the policy gives up.
+    pub async fn fetch<F>(&self, request: F) -> Result<Fetched, reqwest::Error>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let mut retry = 0;
+        loop {
+            let attempt = match request(&self.client).send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    let delay = retry_after(&response);
+                    response.bytes().await.map(|body| (Fetched { status, body }, delay))
+                }
+                Err(e) => Err(e),
+            };
+
+            let delay = match attempt {
+                Ok((fetched, delay)) if is_retryable_status(fetched.status) && retry < self.policy.max_retries => {
+                    delay.unwrap_or_else(|| self.policy.backoff(retry))
+                }
+                Ok((fetched, _)) => return Ok(fetched),
+                Err(e) if !e.is_builder() && retry < self.policy.max_retries => self.policy.backoff(retry),
+                Err(e) => return Err(e),
+            };
+
+            retry += 1;
+            self.retries.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::storage::write_atomic;
+use crate::CommandError;
+
+const EMOTE_INDEX_FILE: &str = "emote_index.json";
+
+// Where one provider emote lives inside one channel folder
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedEmote {
+    // Channel folder relative to MojifyData, e.g. `7tv_emotes/<channel>`
+    pub channel: String,
+    // File relative to MojifyData, as used in the mapping
+    pub path: String,
+    // Keys the emote is stored under in the mapping
+    pub names: Vec<String>,
+    // The channel's alias and the emote's own name, when they differ
+    #[serde(default)]
+    pub alias: String,
+    #[serde(default)]
+    pub original_name: Option<String>,
+}
+
+// Provider emote id -> every channel folder it was synced into. Lets the sync
+// recognise an emote after a rename instead of downloading it again.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct EmoteIndex {
+    emotes: BTreeMap<String, Vec<IndexedEmote>>,
+}
+
+impl EmoteIndex {
+    // Starts empty when the index is missing or unreadable; it is rebuilt as
+    // channels are synced
+    pub fn load(mojify_data_dir: &Path) -> Self {
+        fs::read_to_string(mojify_data_dir.join(EMOTE_INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, mojify_data_dir: &Path) -> Result<(), CommandError> {
+        write_atomic(&mojify_data_dir.join(EMOTE_INDEX_FILE), serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn find(&self, id: &str, channel: &str) -> Option<&IndexedEmote> {
+        self.emotes.get(id)?.iter().find(|indexed| indexed.channel == channel)
+    }
+
+    // File path -> ids of the emotes of `channel` indexed with that file
+    pub fn owners(&self, channel: &str) -> HashMap<String, Vec<String>> {
+        let mut owners: HashMap<String, Vec<String>> = HashMap::new();
+        for (id, entries) in &self.emotes {
+            for indexed in entries.iter().filter(|indexed| indexed.channel == channel) {
+                owners.entry(indexed.path.clone()).or_default().push(id.clone());
+            }
+        }
+        owners
+    }
+
+    // A file belongs to one emote, so whichever emote was indexed with it
+    // before in the same channel is forgotten
+    pub fn insert(&mut self, id: &str, emote: IndexedEmote) {
+        self.emotes.retain(|other, entries| {
+            if other != id {
+                entries.retain(|indexed| indexed.channel != emote.channel || indexed.path != emote.path);
+            }
+            other == id || !entries.is_empty()
+        });
+        let entries = self.emotes.entry(id.to_string()).or_default();
+        entries.retain(|indexed| indexed.channel != emote.channel);
+        entries.push(emote);
+    }
+
+    // Forgets files that were pruned from disk
+    pub fn remove_paths(&mut self, paths: &[String]) {
+        if paths.is_empty() {
+            return;
+        }
+        for entries in self.emotes.values_mut() {
+            entries.retain(|indexed| !paths.contains(&indexed.path));
+        }
+        self.emotes.retain(|_, entries| !entries.is_empty());
+    }
+}
@@ -0,0 +1,346 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{watch, Mutex as TurnLock, MutexGuard as Turn};
+
+use crate::provider::ChannelInput;
+use crate::report::SyncReport;
+use crate::CommandError;
+
+pub type JobId = u64;
+
+// What a sync job was asked to do
+#[derive(Debug, Clone)]
+pub enum SyncRequest {
+    Channels(Vec<ChannelInput>),
+    EmoteSet(String),
+}
+
+fn same_channel(a: &ChannelInput, b: &ChannelInput) -> bool {
+    a.platform == b.platform && a.id.eq_ignore_ascii_case(&b.id)
+}
+
+impl SyncRequest {
+    pub fn description(&self) -> String {
+        match self {
+            SyncRequest::Channels(channels) => {
+                let names: Vec<String> = channels.iter().map(|channel| channel.to_string()).collect();
+                format!("channels {}", names.join(", "))
+            }
+            SyncRequest::EmoteSet(set_id) => format!("7TV emote set {}", set_id),
+        }
+    }
+
+    // Whether syncing `self` also syncs everything `other` asks for
+    fn covers(&self, other: &SyncRequest) -> bool {
+        match (self, other) {
+            (SyncRequest::Channels(ours), SyncRequest::Channels(theirs)) => {
+                theirs.iter().all(|theirs| ours.iter().any(|ours| same_channel(ours, theirs)))
+            }
+            (SyncRequest::EmoteSet(ours), SyncRequest::EmoteSet(theirs)) => ours == theirs,
+            _ => false,
+        }
+    }
+
+    fn overlaps(&self, other: &SyncRequest) -> bool {
+        match (self, other) {
+            (SyncRequest::Channels(ours), SyncRequest::Channels(theirs)) => {
+                theirs.iter().any(|theirs| ours.iter().any(|ours| same_channel(ours, theirs)))
+            }
+            _ => self.covers(other),
+        }
+    }
+
+    // Adds the channels of `other` that aren't already part of `self`
+    fn merge(&mut self, other: SyncRequest) {
+        if let (SyncRequest::Channels(ours), SyncRequest::Channels(theirs)) = (self, other) {
+            for channel in theirs {
+                if !ours.iter().any(|ours| same_channel(ours, &channel)) {
+                    ours.push(channel);
+                }
+            }
+        }
+    }
+}
+
+// Cancellation and pause flags shared between a job and the commands that
+// control it. The sync checks in at `checkpoint` before starting each channel
+// and each emote, so work already in flight always finishes.
+pub struct JobControl {
+    cancelled: AtomicBool,
+    paused: watch::Sender<bool>,
+}
+
+impl Default for JobControl {
+    fn default() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+            paused: watch::Sender::new(false),
+        }
+    }
+}
+
+impl JobControl {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // Wake up anything parked in `checkpoint` so it can bail out
+        self.paused.send_replace(false);
+    }
+
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Waits while the job is paused. Returns `false` once it is cancelled.
+    pub async fn checkpoint(&self) -> bool {
+        let mut paused = self.paused.subscribe();
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            if !*paused.borrow_and_update() {
+                return true;
+            }
+            if paused.changed().await.is_err() {
+                return !self.is_cancelled();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    // Waiting for the jobs ahead of it to finish
+    Queued,
+    Running,
+    Paused,
+    Cancelling,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Cancelled | JobStatus::Failed)
+    }
+}
+
+// What the frontend gets to see of a job
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSnapshot {
+    pub id: JobId,
+    pub description: String,
+    pub status: JobStatus,
+    pub report: Option<SyncReport>,
+    pub error: Option<String>,
+}
+
+pub struct SyncJob {
+    pub control: JobControl,
+    // Queued jobs can still grow when overlapping requests are merged in
+    request: Mutex<SyncRequest>,
+    started: AtomicBool,
+    snapshot: watch::Sender<JobSnapshot>,
+}
+
+impl SyncJob {
+    // Marks the job as running and hands out the request as it stands now
+    pub fn start(&self) -> SyncRequest {
+        self.started.store(true, Ordering::SeqCst);
+        self.snapshot.send_modify(|snapshot| {
+            if snapshot.status == JobStatus::Queued {
+                snapshot.status = JobStatus::Running;
+            }
+        });
+        self.request.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn is_started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    pub fn snapshot(&self) -> JobSnapshot {
+        self.snapshot.borrow().clone()
+    }
+
+    fn set_status(&self, status: JobStatus) {
+        self.snapshot.send_modify(|snapshot| {
+            if !snapshot.status.is_finished() {
+                snapshot.status = status;
+            }
+        });
+    }
+
+    pub fn finish(&self, result: &Result<SyncReport, CommandError>) {
+        self.snapshot.send_modify(|snapshot| {
+            match result {
+                Ok(report) => {
+                    snapshot.status = if report.cancelled { JobStatus::Cancelled } else { JobStatus::Completed };
+                    snapshot.report = Some(report.clone());
+                }
+                Err(e) => {
+                    snapshot.status = JobStatus::Failed;
+                    snapshot.error = Some(e.to_string());
+                }
+            }
+        });
+    }
+
+    // Resolves once the job has finished, with its final snapshot
+    pub async fn wait(&self) -> JobSnapshot {
+        let mut snapshot = self.snapshot.subscribe();
+        let finished = match snapshot.wait_for(|snapshot| snapshot.status.is_finished()).await {
+            Ok(finished) => finished.clone(),
+            Err(_) => self.snapshot(),
+        };
+        finished
+    }
+}
+
+// Tauri-managed queue of sync jobs, keyed by the id handed to the frontend.
+// Jobs run one at a time in the order they were queued.
+#[derive(Default)]
+pub struct SyncJobs {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<JobId, Arc<SyncJob>>>,
+    turn: TurnLock<()>,
+}
+
+impl SyncJobs {
+    // Queues `request`, unless a job already covers it: a running or queued
+    // job with all of its channels is reused as is, and a queued job sharing
+    // some of them absorbs the rest. The flag is `true` for a new job.
+    pub fn enqueue(&self, request: SyncRequest) -> (Arc<SyncJob>, bool) {
+        let mut jobs = self.lock();
+        let mut pending: Vec<&Arc<SyncJob>> =
+            jobs.values().filter(|job| !job.snapshot().status.is_finished() && !job.control.is_cancelled()).collect();
+        pending.sort_by_key(|job| job.snapshot().id);
+
+        for job in &pending {
+            let mut queued = job.request.lock().unwrap_or_else(|e| e.into_inner());
+            if queued.covers(&request) {
+                return (Arc::clone(job), false);
+            }
+            if !job.is_started() && queued.overlaps(&request) {
+                queued.merge(request);
+                let description = queued.description();
+                job.snapshot.send_modify(|snapshot| snapshot.description = description);
+                return (Arc::clone(job), false);
+            }
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let job = Arc::new(SyncJob {
+            control: JobControl::default(),
+            snapshot: watch::Sender::new(JobSnapshot {
+                id,
+                description: request.description(),
+                status: JobStatus::Queued,
+                report: None,
+                error: None,
+            }),
+            request: Mutex::new(request),
+            started: AtomicBool::new(false),
+        });
+        jobs.insert(id, job.clone());
+        (job, true)
+    }
+
+    // Waits until every job queued earlier has finished. The turn passes on
+    // when the returned guard is dropped.
+    pub async fn take_turn(&self) -> Turn<'_, ()> {
+        self.turn.lock().await
+    }
+
+    // Every job this session, oldest first
+    pub fn list(&self) -> Vec<JobSnapshot> {
+        let mut snapshots: Vec<JobSnapshot> = self.lock().values().map(|job| job.snapshot()).collect();
+        snapshots.sort_by_key(|snapshot| snapshot.id);
+        snapshots
+    }
+
+    pub fn get(&self, id: JobId) -> Result<Arc<SyncJob>, CommandError> {
+        self.lock().get(&id).cloned().ok_or(CommandError::UnknownJob(id))
+    }
+
+    pub fn cancel(&self, id: JobId) -> Result<JobSnapshot, CommandError> {
+        let job = self.get(id)?;
+        job.control.cancel();
+        job.set_status(JobStatus::Cancelling);
+        Ok(job.snapshot())
+    }
+
+    pub fn pause(&self, id: JobId) -> Result<JobSnapshot, CommandError> {
+        let job = self.get(id)?;
+        job.control.pause();
+        job.set_status(JobStatus::Paused);
+        Ok(job.snapshot())
+    }
+
+    pub fn resume(&self, id: JobId) -> Result<JobSnapshot, CommandError> {
+        let job = self.get(id)?;
+        if !job.control.is_cancelled() {
+            job.control.resume();
+            job.set_status(if job.is_started() { JobStatus::Running } else { JobStatus::Queued });
+        }
+        Ok(job.snapshot())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<JobId, Arc<SyncJob>>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(inputs: &[&str]) -> SyncRequest {
+        SyncRequest::Channels(inputs.iter().map(|input| ChannelInput::parse(input).unwrap()).collect())
+    }
+
+    #[test]
+    fn requests_cover_their_subsets() {
+        let request = channels(&["xqc", "kick:somestreamer"]);
+        assert!(request.covers(&channels(&["XQC"])));
+        assert!(request.covers(&channels(&["kick:somestreamer", "xqc"])));
+        assert!(!request.covers(&channels(&["xqc", "forsen"])));
+        assert!(!request.covers(&channels(&["somestreamer"])));
+        assert!(!request.covers(&SyncRequest::EmoteSet("xqc".to_string())));
+
+        let set = SyncRequest::EmoteSet("01ABC".to_string());
+        assert!(set.covers(&SyncRequest::EmoteSet("01ABC".to_string())));
+        assert!(!set.covers(&SyncRequest::EmoteSet("01XYZ".to_string())));
+    }
+
+    #[test]
+    fn requests_overlap_on_any_shared_channel() {
+        let request = channels(&["xqc", "forsen"]);
+        assert!(request.overlaps(&channels(&["forsen", "pokimane"])));
+        assert!(!request.overlaps(&channels(&["pokimane"])));
+        assert!(!request.overlaps(&SyncRequest::EmoteSet("01ABC".to_string())));
+        assert!(SyncRequest::EmoteSet("01ABC".to_string()).overlaps(&SyncRequest::EmoteSet("01ABC".to_string())));
+    }
+
+    #[test]
+    fn merging_adds_only_new_channels() {
+        let mut request = channels(&["xqc", "forsen"]);
+        request.merge(channels(&["FORSEN", "pokimane"]));
+        assert_eq!(request.description(), "channels xqc, forsen, pokimane");
+
+        let mut set = SyncRequest::EmoteSet("01ABC".to_string());
+        set.merge(channels(&["xqc"]));
+        assert_eq!(set.description(), "7TV emote set 01ABC");
+    }
+}
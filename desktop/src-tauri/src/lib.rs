@@ -1,113 +1,46 @@
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
-use tauri::{AppHandle, Manager, Window, Wry, Emitter};
-use regex::Regex;
-
-#[derive(Debug, Deserialize, Serialize)]
-struct SevenTvEmoteFile {
-    name: String,
-    static_name: String,
-    width: u32,
-    height: u32,
-    frame_count: u32,
-    size: u64,
-    format: String,
-}
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State, Window, Wry, Emitter};
 
-#[derive(Debug, Deserialize, Serialize)]
-struct SevenTvEmoteHost {
-    url: String,
-    files: Vec<SevenTvEmoteFile>,
-}
+mod bttv;
+mod download;
+mod events;
+mod ffz;
+mod history;
+mod http;
+mod index;
+mod jobs;
+mod mapping;
+mod paths;
+mod provider;
+mod prune;
+mod report;
+mod settings;
+mod snapshots;
+mod storage;
+mod seventv;
+mod sync;
+mod twitch;
 
-#[derive(Debug, Deserialize, Serialize)]
-struct SevenTvEmoteOwnerConnection {
-    id: String,
-    platform: String,
-    username: String,
-    display_name: String,
-    linked_at: u64,
-    emote_capacity: u32,
-    emote_set_id: Option<String>,
-}
-
-#[derive(Debug, Default, Deserialize, Serialize)]
-struct SevenTvEmoteOwnerStyle {
-    #[serde(flatten)]
-    #[serde(default)]
-    extra: HashMap<String, Value>,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct SevenTvEmoteOwner {
-    id: String,
-    username: String,
-    display_name: String,
-    avatar_url: Option<String>,
-    style: SevenTvEmoteOwnerStyle,
-    role_ids: Option<Vec<String>>,
-    connections: Vec<SevenTvEmoteOwnerConnection>,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct SevenTvEmoteData {
-    id: String,
-    name: String,
-    flags: u32,
-    tags: Option<Vec<String>>,
-    lifecycle: u32,
-    state: Vec<String>,
-    listed: bool,
-    animated: bool,
-    owner: Option<SevenTvEmoteOwner>,
-    host: SevenTvEmoteHost,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct SevenTvEmote {
-    id: String,
-    name: String,
-    flags: u32,
-    timestamp: u64,
-    actor_id: Option<String>,
-    data: SevenTvEmoteData,
-    origin_id: Option<String>,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct SevenTvEmoteSet {
-    id: String,
-    name: String,
-    flags: u32,
-    tags: Option<Vec<String>>,
-    immutable: bool,
-    privileged: bool,
-    emotes: Vec<SevenTvEmote>,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct SevenTvUserResponse {
-    id: String,
-    platform: String,
-    username: String,
-    display_name: String,
-    linked_at: u64,
-    emote_capacity: u32,
-    emote_set_id: Option<String>,
-    emote_set: Option<SevenTvEmoteSet>,
-}
-
-// We might need a struct for user details if we fetch that part
-// #[derive(Deserialize, Debug)]
-// struct SevenTvUser {
-//     id: String,
-//     username: String,
-//     display_name: String,
-// }
+use bttv::BttvProvider;
+use ffz::FfzProvider;
+use history::{append_history, load_history};
+use mapping::{load_mapping, save_mapping};
+use provider::{ChannelInput, EmoteProvider, Platform};
+use report::SyncReport;
+use settings::{load_settings, save_settings, Settings};
+use snapshots::{diff_snapshots, list_snapshot_channels, load_snapshots, SnapshotChannel, SnapshotDiff};
+use seventv::{SevenTvProvider, SEVENTV_API_BASE};
+use download::HostLimiter;
+use events::TextLog;
+use http::{HttpClient, RetryPolicy};
+use index::EmoteIndex;
+use jobs::{JobControl, JobId, JobSnapshot, SyncJob, SyncJobs, SyncRequest};
+use storage::DataDirLock;
+use sync::{log_overall_summary, sync_targets, SyncContext, SyncTarget, SyncTotals};
+use twitch::{is_twitch_id, ChannelIdResolver};
 
 // Error type for our command
 #[derive(Debug, thiserror::Error)]
@@ -122,6 +55,26 @@ enum CommandError {
     TauriPath(String),
     #[error("Invalid channel ID list")]
     InvalidChannelIds,
+    #[error("Unsupported platform prefix: {0}")]
+    UnsupportedPlatform(String),
+    #[error("Could not find a Twitch user named {0}")]
+    UnknownTwitchLogin(String),
+    #[error("Could not find a Kick channel named {0} on 7TV")]
+    UnknownKickChannel(String),
+    #[error("Invalid 7TV emote set ID")]
+    InvalidEmoteSetId,
+    #[error("No sync job with ID {0}")]
+    UnknownJob(JobId),
+    #[error("Sync failed: {0}")]
+    SyncFailed(String),
+    #[error("No such snapshot: {0}")]
+    SnapshotNotFound(String),
+    #[error("The mapping file {0} is damaged and has no usable backup")]
+    CorruptMapping(String),
+    #[error("Unsafe path component: {0:?}")]
+    UnsafePath(String),
+    #[error("HTTP {status} response from {url}")]
+    HttpStatus { status: u16, url: String },
 }
 
 // Required for Tauri to serialize errors back to frontend
@@ -134,281 +87,441 @@ impl serde::Serialize for CommandError {
     }
 }
 
-// Helper function to emit log events to the frontend
+// Helper function to emit log events to the frontend. The typed `sync://`
+// events are the primary progress signal; this text stream can be turned off
+// with `Settings::text_log`.
 fn emit_log(window: &Window<Wry>, message: String) {
+    if window.try_state::<TextLog>().is_some_and(|text_log| !text_log.enabled()) {
+        return;
+    }
     if let Err(e) = window.emit("download://log", message) {
         eprintln!("Failed to emit log event: {}", e);
     }
 }
 
-fn sanitize_filename(filename: &str) -> String {
-    let invalid_chars = Regex::new("[<>:/\\|?*]").unwrap();
-    invalid_chars.replace_all(filename, "_").into_owned()
+fn mojify_data_dir(app_handle: &AppHandle) -> Result<PathBuf, CommandError> {
+    let app_data_dir = app_handle
+        .path()
+        .app_local_data_dir()
+        .map_err(|e| CommandError::TauriPath(format!("Failed to get app local data directory: {}", e)))?;
+    Ok(app_data_dir.join("MojifyData"))
 }
 
-#[tauri::command]
-async fn download_emotes_command(
-    app_handle: AppHandle,
-    window: Window,
-    channel_ids_str: String,
-) -> Result<String, CommandError> {
-    emit_log(&window, format!("Backend: Command started. IDs: {}", channel_ids_str));
+fn http_client(retry: RetryPolicy) -> Result<HttpClient, CommandError> {
+    let client = reqwest::Client::builder()
+        .user_agent("MojifyApp/0.1")
+        .build()?;
+    Ok(HttpClient::new(client, retry))
+}
+
+// Swaps Twitch logins and Kick channel names for numeric ids. Channels whose
+// name can't be resolved are logged and dropped so the rest of the sync can
+// go ahead. A channel entered twice, e.g. as `xqc` and by its id, is only
+// kept once so it never syncs into the same folder side by side.
+async fn resolve_channel_names(
+    window: &Window<Wry>,
+    client: &HttpClient,
+    resolver: &mut ChannelIdResolver,
+    channels: Vec<ChannelInput>,
+) -> Vec<ChannelInput> {
+    let mut resolved = Vec::with_capacity(channels.len());
+    for channel in channels {
+        if is_twitch_id(&channel.id) {
+            resolved.push(channel);
+            continue;
+        }
+        let user_id = match channel.platform {
+            Platform::Twitch => resolver.resolve(window, client, &channel.id).await,
+            Platform::Kick => resolver.resolve_kick(window, client, &channel.id).await,
+            Platform::YouTube => {
+                resolved.push(channel);
+                continue;
+            }
+        };
+        match user_id {
+            Ok(user_id) => {
+                emit_log(window, format!("Backend: Resolved {} to ID {}", channel, user_id));
+                resolved.push(channel.with_user_id(user_id));
+            }
+            Err(e) => emit_log(window, format!("Backend: Skipping channel {}: {}", channel, e)),
+        }
+    }
 
+    let mut unique: Vec<ChannelInput> = Vec::with_capacity(resolved.len());
+    for channel in resolved {
+        match unique.iter_mut().find(|kept| kept.platform == channel.platform && kept.id == channel.id) {
+            Some(kept) => {
+                emit_log(window, format!("Backend: {} is listed more than once, syncing it once", channel));
+                // Keep the name for the namespace, whichever entry had it
+                if kept.login.is_none() {
+                    kept.login = channel.login;
+                }
+            }
+            None => unique.push(channel),
+        }
+    }
+    unique
+}
+
+// Waits until no other sync (in this app or another instance) is using the
+// data directory. Gives up early if the job is cancelled meanwhile.
+async fn lock_data_dir(
+    window: &Window<Wry>,
+    mojify_data_dir: &Path,
+    control: &JobControl,
+) -> Result<Option<DataDirLock>, CommandError> {
+    let mut waiting = false;
+    loop {
+        if control.is_cancelled() {
+            return Ok(None);
+        }
+        if let Some(lock) = DataDirLock::try_acquire(mojify_data_dir)? {
+            return Ok(Some(lock));
+        }
+        if !waiting {
+            emit_log(window, "Backend: Another sync is using MojifyData, waiting for it to finish...".to_string());
+            waiting = true;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+fn parse_channel_ids(window: &Window<Wry>, channel_ids_str: &str) -> Result<SyncRequest, CommandError> {
     let channel_ids: Vec<&str> = channel_ids_str.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
     if channel_ids.is_empty() {
-        emit_log(&window, "Backend: No valid channel IDs provided.".to_string());
+        emit_log(window, "Backend: No valid channel IDs provided.".to_string());
         return Err(CommandError::InvalidChannelIds);
     }
-    emit_log(&window, format!("Backend: Parsed Channel IDs: {:?}", channel_ids));
+    // A bad entry is skipped rather than failing the channels next to it
+    let channels: Vec<ChannelInput> = channel_ids
+        .iter()
+        .filter_map(|id| {
+            ChannelInput::parse(id)
+                .inspect_err(|e| emit_log(window, format!("Backend: Skipping channel {:?}: {}", id, e)))
+                .ok()
+        })
+        .collect();
+    if channels.is_empty() {
+        return Err(CommandError::InvalidChannelIds);
+    }
+    emit_log(window, format!("Backend: Parsed Channel IDs: {:?}", channel_ids));
+    Ok(SyncRequest::Channels(channels))
+}
 
-    let app_data_dir_result = app_handle.path().app_local_data_dir();
-    emit_log(&window, format!("Backend: app_local_data_dir result: {:?}", app_data_dir_result));
-    let app_data_dir = app_data_dir_result
-        .map_err(|e| CommandError::TauriPath(format!("Failed to get app local data directory: {}", e)))?;
-    
-    let mojify_data_dir = app_data_dir.join("MojifyData");
-    let emotes_base_dir = mojify_data_dir.join("7tv_emotes");
+fn parse_emote_set_id(window: &Window<Wry>, set_id: &str) -> Result<SyncRequest, CommandError> {
+    let set_id = set_id.trim();
+    if set_id.is_empty() || !set_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        emit_log(window, format!("Backend: Invalid 7TV emote set ID: {:?}", set_id));
+        return Err(CommandError::InvalidEmoteSetId);
+    }
+    Ok(SyncRequest::EmoteSet(set_id.to_string()))
+}
+
+async fn run_sync(
+    app_handle: &AppHandle,
+    window: &Window<Wry>,
+    request: SyncRequest,
+    job_id: JobId,
+    control: &JobControl,
+) -> Result<SyncReport, CommandError> {
+    let started = Instant::now();
+    let started_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let description = request.description();
+    let mojify_data_dir = mojify_data_dir(app_handle)?;
     let mapping_file_path = mojify_data_dir.join("emote_mapping.json");
-    emit_log(&window, format!("Backend: MojifyData dir path: {}", mojify_data_dir.display()));
-    emit_log(&window, format!("Backend: Emotes base dir path: {}", emotes_base_dir.display()));
-    emit_log(&window, format!("Backend: Mapping file path: {}", mapping_file_path.display()));
+    emit_log(window, format!("Backend: MojifyData dir path: {}", mojify_data_dir.display()));
+    emit_log(window, format!("Backend: Mapping file path: {}", mapping_file_path.display()));
+    fs::create_dir_all(&mojify_data_dir)?;
 
-    emit_log(&window, "Backend: Attempting to create emotes base directory...".to_string());
-    fs::create_dir_all(&emotes_base_dir)?;
-    emit_log(&window, "Backend: Emotes base directory created/ensured.".to_string());
+    // Held until the mapping is written, so overlapping syncs can't read the
+    // same mapping and clobber each other's result
+    let Some(_lock) = lock_data_dir(window, &mojify_data_dir, control).await? else {
+        emit_log(window, "Backend: Download command cancelled.".to_string());
+        return Ok(SyncReport {
+            job_id,
+            description,
+            started_at,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            cancelled: true,
+            totals: SyncTotals::default(),
+            channels: Vec::new(),
+        });
+    };
 
-    let client = reqwest::Client::builder()
-        .user_agent("MojifyApp/0.1")
-        .build()?;
-    emit_log(&window, "Backend: Reqwest client created.".to_string());
-
-    let mut global_emote_mapping: HashMap<String, String> = 
-        if mapping_file_path.exists() {
-            emit_log(&window, "Backend: Mapping file exists, attempting to read...".to_string());
-            let content = fs::read_to_string(&mapping_file_path)?;
-            emit_log(&window, "Backend: Read mapping file content.".to_string());
-            serde_json::from_str(&content).unwrap_or_else(|e| {
-                emit_log(&window, format!("Backend: Error parsing existing mapping JSON, starting fresh: {}", e));
-                HashMap::new()
-            })
-        } else {
-            emit_log(&window, "Backend: No existing mapping file found, starting fresh.".to_string());
-            HashMap::new()
-        };
-    emit_log(&window, format!("Backend: Initial global mapping loaded with {} entries.", global_emote_mapping.len()));
-
-    // Stats tracking variables
-    let mut total_emotes_processed = 0;
-    let mut total_emotes_downloaded = 0;
-    let mut total_emotes_skipped = 0;
-    let mut total_emotes_failed = 0;
-    let mut failed_emotes = Vec::new();
-
-    for channel_id in channel_ids {
-        emit_log(&window, format!("Backend: Processing channel ID: {}", channel_id));
-        let api_url = format!("https://7tv.io/v3/users/twitch/{}", channel_id);
-        emit_log(&window, format!("Backend: Fetching from API URL: {}", api_url));
-        
-        // Channel-specific stats
-        let mut channel_emotes_processed = 0;
-        let mut channel_emotes_downloaded = 0;
-        let mut channel_emotes_skipped = 0;
-        let mut channel_emotes_failed = 0;
-        let mut channel_failed_emotes = Vec::new();
-        
-        match client.get(&api_url).send().await {
-            Ok(response) => {
-                emit_log(&window, format!("Backend: API response status for {}: {}", channel_id, response.status()));
-                if response.status().is_success() {
-                    match response.text().await {
-                        Ok(raw_json_text) => {
-                            let snippet_len = raw_json_text.chars().take(1000).collect::<String>().len();
-                            emit_log(&window, format!("Backend: Received JSON text (snippet {} chars): {}", snippet_len, raw_json_text.chars().take(1000).collect::<String>()));
-                            
-                            match serde_json::from_str::<SevenTvUserResponse>(&raw_json_text) {
-                                Ok(user_response) => {
-                                    emit_log(&window, format!("Backend: Successfully parsed JSON for channel: {}", channel_id));
-                                    if let Some(emote_set) = user_response.emote_set {
-                                        if emote_set.emotes.is_empty() {
-                                            emit_log(&window, format!("Backend: No emotes in set for channel {}", channel_id));
-                                            continue;
-                                        }
-                                        emit_log(&window, format!("Backend: Found {} emotes for {}. Emote set ID: {}", emote_set.emotes.len(), channel_id, emote_set.id));
-                                        
-                                        let channel_emote_dir = emotes_base_dir.join(channel_id);
-                                        fs::create_dir_all(&channel_emote_dir)?;
-
-                                        for emote in emote_set.emotes {
-                                            channel_emotes_processed += 1;
-                                            total_emotes_processed += 1;
-                                            
-                                            emit_log(&window, format!("Backend: Processing emote: {} (ID: {})", emote.name, emote.id));
-                                            
-                                            // Check if emote has GIF format available by examining the files array
-                                            let emote_id = &emote.id;
-                                            let emote_data = &emote.data;
-                                            
-                                            // Check if 4x.gif exists in the files array
-                                            let has_gif = emote_data.host.files.iter().any(|file| file.name == "4x.gif");
-                                            
-                                            // Choose format based on availability
-                                            let (file_extension, format_name) = if has_gif {
-                                                ("gif", "GIF")
-                                            } else {
-                                                ("png", "PNG")
-                                            };
-                                            
-                                            let download_url = format!("https://cdn.7tv.app/emote/{}/4x.{}", emote_id, file_extension);
-                                            emit_log(&window, format!("Backend: Using {} format. Constructed download URL: {}", format_name, download_url));
-
-                                            let sanitized_emote_name = sanitize_filename(&emote.name);
-                                            let emote_filename = format!("{}.{}", sanitized_emote_name, file_extension);
-                                            let output_path = channel_emote_dir.join(&emote_filename);
-
-                                            if output_path.exists() {
-                                                emit_log(&window, format!("Backend: Skipping existing emote: {} at {}", emote.name, output_path.display()));
-                                                let mapping_key = format!(":{}:", sanitized_emote_name);
-                                                if !global_emote_mapping.contains_key(&mapping_key) {
-                                                    let relative_path = Path::new("7tv_emotes").join(channel_id).join(&emote_filename);
-                                                    global_emote_mapping.insert(mapping_key, relative_path.to_string_lossy().replace("\\", "/"));
-                                                    emit_log(&window, format!("Backend: Added skipped existing emote {} to map.", emote.name));
-                                                }
-                                                channel_emotes_skipped += 1;
-                                                total_emotes_skipped += 1;
-                                                continue;
-                                            }
-
-                                            emit_log(&window, format!("Backend: Attempting to download {}: {} to {}", emote.name, download_url, output_path.display()));
-                                            match client.get(&download_url).send().await {
-                                                Ok(emote_response) => {
-                                                    if emote_response.status().is_success() {
-                                                        match emote_response.bytes().await {
-                                                            Ok(emote_bytes) => {
-                                                                match File::create(&output_path) {
-                                                                    Ok(mut file) => {
-                                                                        match file.write_all(&emote_bytes) {
-                                                                            Ok(_) => {
-                                                                                emit_log(&window, format!("Backend: Saved {} ({} bytes) to {}", emote.name, emote_bytes.len(), output_path.display()));
-                                                                                
-                                                                                let relative_path = Path::new("7tv_emotes").join(channel_id).join(&emote_filename);
-                                                                                global_emote_mapping.insert(format!(":{}:", sanitized_emote_name), relative_path.to_string_lossy().replace("\\", "/"));
-                                                                                emit_log(&window, format!("Backend: Added {} to map.", emote.name));
-                                                                                
-                                                                                channel_emotes_downloaded += 1;
-                                                                                total_emotes_downloaded += 1;
-                                                                            },
-                                                                            Err(e) => {
-                                                                                let error_msg = format!("Failed to write emote {} to file: {}", emote.name, e);
-                                                                                emit_log(&window, format!("Backend: {}", error_msg));
-                                                                                channel_emotes_failed += 1;
-                                                                                total_emotes_failed += 1;
-                                                                                channel_failed_emotes.push(format!("{}: {}", emote.name, error_msg));
-                                                                                failed_emotes.push(format!("{}: {} ({})", channel_id, emote.name, error_msg));
-                                                                            }
-                                                                        }
-                                                                    },
-                                                                    Err(e) => {
-                                                                        let error_msg = format!("Failed to create file for emote {}: {}", emote.name, e);
-                                                                        emit_log(&window, format!("Backend: {}", error_msg));
-                                                                        channel_emotes_failed += 1;
-                                                                        total_emotes_failed += 1;
-                                                                        channel_failed_emotes.push(format!("{}: {}", emote.name, error_msg));
-                                                                        failed_emotes.push(format!("{}: {} ({})", channel_id, emote.name, error_msg));
-                                                                    }
-                                                                }
-                                                            },
-                                                            Err(e) => {
-                                                                let error_msg = format!("Failed to get bytes for emote {}: {}", emote.name, e);
-                                                                emit_log(&window, format!("Backend: {}", error_msg));
-                                                                channel_emotes_failed += 1;
-                                                                total_emotes_failed += 1;
-                                                                channel_failed_emotes.push(format!("{}: {}", emote.name, error_msg));
-                                                                failed_emotes.push(format!("{}: {} ({})", channel_id, emote.name, error_msg));
-                                                            }
-                                                        }
-                                                    } else {
-                                                        let error_msg = format!("HTTP {} response", emote_response.status());
-                                                        emit_log(&window, format!("Backend: Failed to download {}: {}", emote.name, error_msg));
-                                                        channel_emotes_failed += 1;
-                                                        total_emotes_failed += 1;
-                                                        channel_failed_emotes.push(format!("{}: {}", emote.name, error_msg));
-                                                        failed_emotes.push(format!("{}: {} ({})", channel_id, emote.name, error_msg));
-                                                    }
-                                                }
-                                                Err(e) => {
-                                                    let error_msg = format!("HTTP request error: {:#?}", e);
-                                                    emit_log(&window, format!("Backend: HTTP Error downloading emote {}: {}", emote.name, error_msg));
-                                                    channel_emotes_failed += 1;
-                                                    total_emotes_failed += 1;
-                                                    channel_failed_emotes.push(format!("{}: {}", emote.name, error_msg));
-                                                    failed_emotes.push(format!("{}: {} ({})", channel_id, emote.name, error_msg));
-                                                }
-                                            }
-                                        }
-                                    } else {
-                                        emit_log(&window, format!("Backend: Missing emote_set for channel ID {} (user_response.emote_set was None)", channel_id));
-                                    }
-                                }
-                                Err(e) => {
-                                    emit_log(&window, format!("Backend: Error parsing JSON for {}: {}. Full Raw JSON: {}", channel_id, e, raw_json_text));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            emit_log(&window, format!("Backend: Failed to read API response text for {}: {}", channel_id, e));
-                        }
-                    }
-                } else {
-                    emit_log(&window, format!("Backend: API request failed for {}: HTTP {}", channel_id, response.status()));
+    let settings = load_settings(&mojify_data_dir)?;
+    app_handle.state::<TextLog>().set(settings.text_log);
+    let client = http_client(settings.retry)?;
+    emit_log(window, "Backend: Reqwest client created.".to_string());
+
+    let mut targets = Vec::new();
+    match request {
+        SyncRequest::Channels(channels) => {
+            if settings.include_7tv_globals {
+                emit_log(window, "Backend: Including 7TV global emotes.".to_string());
+            }
+
+            let mut resolver = ChannelIdResolver::new(SEVENTV_API_BASE, settings.twitch_lookup_url.clone(), mojify_data_dir.clone());
+            let channels = resolve_channel_names(window, &client, &mut resolver, channels).await;
+
+            let providers: Vec<Arc<dyn EmoteProvider>> = vec![
+                Arc::new(
+                    SevenTvProvider::default()
+                        .with_globals(settings.include_7tv_globals)
+                        .with_formats(settings.format_preference.clone()),
+                ),
+                Arc::new(BttvProvider::default()),
+                Arc::new(FfzProvider::default()),
+            ];
+            for provider in &providers {
+                for channel in &channels {
+                    targets.push(SyncTarget::Input(provider.clone(), channel.clone()));
+                }
+                if let Some(global) = provider.global_channel() {
+                    targets.push(SyncTarget::Resolved(provider.clone(), global));
                 }
             }
-            Err(e) => emit_log(&window, format!("Backend: HTTP Error fetching data for channel {}: {}", channel_id, e)),
         }
-        
-        // Log channel summary
-        emit_log(&window, format!("Backend: === CHANNEL {} SUMMARY ===", channel_id));
-        emit_log(&window, format!("Backend: Total emotes processed: {}", channel_emotes_processed));
-        emit_log(&window, format!("Backend: Successfully downloaded: {} emotes", channel_emotes_downloaded));
-        emit_log(&window, format!("Backend: Skipped (already existed): {} emotes", channel_emotes_skipped));
-        emit_log(&window, format!("Backend: Failed to download: {} emotes", channel_emotes_failed));
-        
-        // If any failures, log them in a summarized way
-        if !channel_failed_emotes.is_empty() {
-            emit_log(&window, "Backend: Failed emotes for this channel:".to_string());
-            for (index, failed_emote) in channel_failed_emotes.iter().enumerate() {
-                emit_log(&window, format!("Backend:   {}. {}", index + 1, failed_emote));
-            }
+        SyncRequest::EmoteSet(set_id) => {
+            // Syncs a single 7TV emote set, e.g. a shared team or seasonal
+            // set, into `7tv_emotes/set_<id>` regardless of which channel (if
+            // any) uses it
+            let provider = Arc::new(SevenTvProvider::default().with_formats(settings.format_preference.clone()));
+            let channel = provider.emote_set_channel(&set_id);
+            targets.push(SyncTarget::Resolved(provider, channel));
         }
     }
 
-    emit_log(&window, "Backend: Attempting to serialize final mapping...".to_string());
-    let mapping_json = serde_json::to_string_pretty(&global_emote_mapping)?;
-    emit_log(&window, "Backend: Attempting to write final mapping to file...".to_string());
-    fs::write(&mapping_file_path, mapping_json)?;
-    emit_log(&window, format!("Backend: Emote mapping saved to {}", mapping_file_path.display()));
-    
-    // Log overall summary
-    emit_log(&window, "Backend: ======= OVERALL SUMMARY =======".to_string());
-    emit_log(&window, format!("Backend: Total emotes processed: {}", total_emotes_processed));
-    emit_log(&window, format!("Backend: Successfully downloaded: {} emotes", total_emotes_downloaded));
-    emit_log(&window, format!("Backend: Skipped (already existed): {} emotes", total_emotes_skipped));
-    emit_log(&window, format!("Backend: Failed to download: {} emotes", total_emotes_failed));
-    
-    // If any failures, log them in a summarized way
-    if !failed_emotes.is_empty() {
-        emit_log(&window, "Backend: Failed emotes (limited to first 50):".to_string());
-        for (index, failed_emote) in failed_emotes.iter().take(50).enumerate() {
-            emit_log(&window, format!("Backend:   {}. {}", index + 1, failed_emote));
-        }
-        
-        if failed_emotes.len() > 50 {
-            emit_log(&window, format!("Backend: ... and {} more failed emotes", failed_emotes.len() - 50));
+    let mut global_emote_mapping = load_mapping(window, &mapping_file_path)?;
+    emit_log(window, format!("Backend: Initial global mapping loaded with {} entries.", global_emote_mapping.len()));
+
+    let index = Mutex::new(EmoteIndex::load(&mojify_data_dir));
+    let limiter = HostLimiter::new(settings.max_downloads_per_host);
+    let ctx = SyncContext {
+        window,
+        job_id,
+        client: &client,
+        mojify_data_dir: &mojify_data_dir,
+        limiter: &limiter,
+        control,
+        prune_mode: settings.prune_mode,
+        emote_names: settings.emote_names,
+        channel_priority: &settings.channel_priority,
+        scales: &settings.scales,
+        index: &index,
+        emote_concurrency: settings.max_parallel_emotes,
+        channel_concurrency: settings.max_parallel_channels,
+    };
+    let mut totals = SyncTotals::default();
+    let channels = sync_targets(&ctx, targets, &mut global_emote_mapping, &mut totals).await;
+
+    // Whatever finished before a cancel is already in the mapping, so it is
+    // saved the same way as a complete run. `reduce_motion` may have been
+    // toggled while the sync ran, which `update_settings` leaves to us.
+    let reduce_motion = load_settings(&mojify_data_dir).map_or(settings.reduce_motion, |latest| latest.reduce_motion);
+    save_mapping(window, &mapping_file_path, &global_emote_mapping, reduce_motion)?;
+    index.into_inner().unwrap_or_else(|e| e.into_inner()).save(&mojify_data_dir)?;
+    log_overall_summary(window, &totals);
+
+    let report = SyncReport {
+        job_id,
+        description,
+        started_at,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        cancelled: control.is_cancelled(),
+        totals,
+        channels,
+    };
+    // A sync that worked shouldn't be reported as failed over its history
+    if let Err(e) = append_history(&mojify_data_dir, &report) {
+        emit_log(window, format!("Backend: Failed to save sync history: {}", e));
+    }
+
+    if report.cancelled {
+        emit_log(window, "Backend: Download command cancelled.".to_string());
+    } else {
+        emit_log(window, "Backend: Download command finished successfully.".to_string());
+    }
+    Ok(report)
+}
+
+// Queues `request` and runs it in the background once the jobs ahead of it
+// are done. Requests already covered by a pending job join that job instead.
+fn start_job(app_handle: AppHandle, window: Window, jobs: &SyncJobs, request: SyncRequest) -> Arc<SyncJob> {
+    let (job, is_new) = jobs.enqueue(request);
+    let snapshot = job.snapshot();
+    if !is_new {
+        emit_log(&window, format!("Backend: Merged into sync job {} for {}", snapshot.id, snapshot.description));
+        return job;
+    }
+    emit_log(&window, format!("Backend: Sync job {} queued for {}", snapshot.id, snapshot.description));
+
+    let task_job = job.clone();
+    tauri::async_runtime::spawn(async move {
+        let job_id = task_job.snapshot().id;
+        let jobs = app_handle.state::<SyncJobs>();
+        let _turn = jobs.take_turn().await;
+        let request = task_job.start();
+        emit_log(&window, format!("Backend: Sync job {} started for {}", job_id, request.description()));
+        let result = run_sync(&app_handle, &window, request, job_id, &task_job.control).await;
+        if let Err(e) = &result {
+            emit_log(&window, format!("Backend: Sync job {} failed: {}", job_id, e));
         }
+        task_job.finish(&result);
+    });
+    job
+}
+
+async fn wait_for_job(job: &SyncJob) -> Result<SyncReport, CommandError> {
+    let snapshot = job.wait().await;
+    match (snapshot.report, snapshot.error) {
+        (Some(report), _) => Ok(report),
+        (None, error) => Err(CommandError::SyncFailed(error.unwrap_or_default())),
+    }
+}
+
+// Runs a sync and waits for it to finish. `start_sync` is the non-blocking
+// variant that hands back a job ID instead.
+#[tauri::command]
+async fn download_emotes_command(
+    app_handle: AppHandle,
+    window: Window,
+    jobs: State<'_, SyncJobs>,
+    channel_ids_str: String,
+) -> Result<SyncReport, CommandError> {
+    emit_log(&window, format!("Backend: Command started. IDs: {}", channel_ids_str));
+    let request = parse_channel_ids(&window, &channel_ids_str)?;
+    let job = start_job(app_handle, window, &jobs, request);
+    wait_for_job(&job).await
+}
+
+// Syncs a 7TV emote set by ID and waits for it to finish
+#[tauri::command]
+async fn download_emote_set_command(
+    app_handle: AppHandle,
+    window: Window,
+    jobs: State<'_, SyncJobs>,
+    set_id: String,
+) -> Result<SyncReport, CommandError> {
+    emit_log(&window, format!("Backend: Emote set command started. Set ID: {}", set_id));
+    let request = parse_emote_set_id(&window, &set_id)?;
+    let job = start_job(app_handle, window, &jobs, request);
+    wait_for_job(&job).await
+}
+
+#[tauri::command]
+async fn start_sync(
+    app_handle: AppHandle,
+    window: Window,
+    jobs: State<'_, SyncJobs>,
+    channel_ids_str: String,
+) -> Result<JobId, CommandError> {
+    let request = parse_channel_ids(&window, &channel_ids_str)?;
+    Ok(start_job(app_handle, window, &jobs, request).snapshot().id)
+}
+
+#[tauri::command]
+async fn start_emote_set_sync(
+    app_handle: AppHandle,
+    window: Window,
+    jobs: State<'_, SyncJobs>,
+    set_id: String,
+) -> Result<JobId, CommandError> {
+    let request = parse_emote_set_id(&window, &set_id)?;
+    Ok(start_job(app_handle, window, &jobs, request).snapshot().id)
+}
+
+// Running, queued and finished jobs, oldest first
+#[tauri::command]
+async fn list_sync_jobs(jobs: State<'_, SyncJobs>) -> Result<Vec<JobSnapshot>, CommandError> {
+    Ok(jobs.list())
+}
+
+#[tauri::command]
+async fn get_sync_job(jobs: State<'_, SyncJobs>, job_id: JobId) -> Result<JobSnapshot, CommandError> {
+    Ok(jobs.get(job_id)?.snapshot())
+}
+
+// Stops a job once its in-flight downloads finish; the mapping is still
+// written with everything completed up to that point
+#[tauri::command]
+async fn cancel_sync(jobs: State<'_, SyncJobs>, job_id: JobId) -> Result<JobSnapshot, CommandError> {
+    jobs.cancel(job_id)
+}
+
+#[tauri::command]
+async fn pause_sync(jobs: State<'_, SyncJobs>, job_id: JobId) -> Result<JobSnapshot, CommandError> {
+    jobs.pause(job_id)
+}
+
+#[tauri::command]
+async fn resume_sync(jobs: State<'_, SyncJobs>, job_id: JobId) -> Result<JobSnapshot, CommandError> {
+    jobs.resume(job_id)
+}
+
+// Reports of past syncs, oldest first
+#[tauri::command]
+async fn get_sync_history(app_handle: AppHandle) -> Result<Vec<SyncReport>, CommandError> {
+    Ok(load_history(&mojify_data_dir(&app_handle)?))
+}
+
+// Channels that have recorded emote snapshots
+#[tauri::command]
+async fn get_snapshot_channels(app_handle: AppHandle) -> Result<Vec<SnapshotChannel>, CommandError> {
+    list_snapshot_channels(&mojify_data_dir(&app_handle)?)
+}
+
+// Emotes added, removed and renamed between two snapshots of a channel.
+// `from` and `to` index the channel's snapshots oldest first and default to
+// the latest two.
+#[tauri::command]
+async fn diff_channel_snapshots(
+    app_handle: AppHandle,
+    folder: String,
+    dir_name: String,
+    from: Option<usize>,
+    to: Option<usize>,
+) -> Result<SnapshotDiff, CommandError> {
+    let snapshots = load_snapshots(&mojify_data_dir(&app_handle)?, &folder, &dir_name)?;
+    let to = to.unwrap_or(snapshots.len().saturating_sub(1));
+    let from = from.unwrap_or(to.saturating_sub(1));
+    let snapshot = |index: usize| {
+        snapshots
+            .get(index)
+            .ok_or_else(|| CommandError::SnapshotNotFound(format!("{}/{} #{}", folder, dir_name, index)))
+    };
+    Ok(diff_snapshots(snapshot(from)?, snapshot(to)?))
+}
+
+#[tauri::command]
+async fn get_settings(app_handle: AppHandle) -> Result<Settings, CommandError> {
+    load_settings(&mojify_data_dir(&app_handle)?)
+}
+
+#[tauri::command]
+async fn update_settings(app_handle: AppHandle, window: Window, settings: Settings) -> Result<Settings, CommandError> {
+    let mojify_data_dir = mojify_data_dir(&app_handle)?;
+    fs::create_dir_all(&mojify_data_dir)?;
+    // Without the lock a sync is running; it reads `reduce_motion` again
+    // right before it writes the mapping, so it applies the change itself
+    let lock = DataDirLock::try_acquire(&mojify_data_dir)?;
+    let previous = load_settings(&mojify_data_dir)?;
+    save_settings(&mojify_data_dir, &settings)?;
+    app_handle.state::<TextLog>().set(settings.text_log);
+    if lock.is_some() && settings.reduce_motion != previous.reduce_motion {
+        rewrite_mapping(&window, &mojify_data_dir, settings.reduce_motion)?;
     }
+    Ok(settings)
+}
 
-    emit_log(&window, "Backend: Download command finished successfully.".to_string());
-    Ok(format!("Download process finished. Summary: {} processed, {} downloaded, {} skipped, {} failed. Check logs for details.", 
-        total_emotes_processed, total_emotes_downloaded, total_emotes_skipped, total_emotes_failed))
+// Points the v1 mapping at the static frames, or back at the full files,
+// from the v2 records. The caller holds the data dir lock.
+fn rewrite_mapping(window: &Window<Wry>, mojify_data_dir: &Path, reduce_motion: bool) -> Result<(), CommandError> {
+    let mapping_file_path = mojify_data_dir.join("emote_mapping.json");
+    let mapping = load_mapping(window, &mapping_file_path)?;
+    if mapping.is_empty() {
+        return Ok(());
+    }
+    save_mapping(window, &mapping_file_path, &mapping, reduce_motion)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -424,7 +537,24 @@ pub fn run() {
       }
       Ok(())
     })
-    .invoke_handler(tauri::generate_handler![download_emotes_command]) // Register the command
+    .manage(SyncJobs::default())
+    .manage(TextLog::default())
+    .invoke_handler(tauri::generate_handler![
+      download_emotes_command,
+      download_emote_set_command,
+      start_sync,
+      start_emote_set_sync,
+      get_sync_job,
+      list_sync_jobs,
+      cancel_sync,
+      pause_sync,
+      resume_sync,
+      get_sync_history,
+      get_snapshot_channels,
+      diff_channel_snapshots,
+      get_settings,
+      update_settings
+    ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
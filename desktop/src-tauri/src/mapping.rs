@@ -0,0 +1,541 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tauri::{Window, Wry};
+
+use crate::storage::{read_json_with_recovery, write_json_with_backup, JsonRead};
+use crate::provider::{EmoteOwner, ProviderEmote};
+use crate::{emit_log, CommandError};
+
+const MAPPING_V2_FILE: &str = "emote_mapping.v2.json";
+const MAPPING_VERSION: u32 = 2;
+
+// What the providers told us about an emote, kept in the v2 mapping
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmoteMeta {
+    pub id: String,
+    pub provider: String,
+    // Namespace of the channel the emote was synced from
+    pub channel: String,
+    pub name: String,
+    pub original_name: Option<String>,
+    pub animated: bool,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub owner: Option<EmoteOwner>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // When the channel added the emote, as a Unix timestamp in milliseconds
+    pub added_at: Option<u64>,
+    // Extension of the file that was stored, e.g. `webp`
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+impl EmoteMeta {
+    pub fn new(provider: &str, channel: &str, emote: &ProviderEmote) -> Self {
+        // The smallest file is the emote's size at 1x
+        let base = emote.files.iter().min_by_key(|file| file.scale);
+        Self {
+            id: emote.id.clone(),
+            provider: provider.to_string(),
+            channel: channel.to_string(),
+            name: emote.code.clone(),
+            original_name: emote.original_name.clone(),
+            animated: emote.animated,
+            width: base.and_then(|file| file.width),
+            height: base.and_then(|file| file.height),
+            owner: emote.owner.clone(),
+            tags: emote.tags.clone(),
+            added_at: emote.added_at,
+            format: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MappingRecord {
+    pub path: String,
+    // Every stored size of the emote by scale, the full-size `path`
+    // included. Only filled in when extra scales are configured.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scales: BTreeMap<u32, String>,
+    // First frame of an animated emote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub static_path: Option<String>,
+    // Missing for entries migrated from v1 until their channel syncs again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<EmoteMeta>,
+}
+
+// `":name:"` -> file and metadata
+pub type EmoteMapping = HashMap<String, MappingRecord>;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct MappingFileV2 {
+    version: u32,
+    emotes: EmoteMapping,
+}
+
+// A key the sync wants in the mapping. Namespaced keys (`:channel/name:`)
+// and bare keys are both resolved by `KeyClaims`; namespaced keys can still
+// collide when a channel has emotes of the same name on two providers.
+// `replace` marks fresh downloads, which take a key over from a channel of
+// the same rank that wasn't part of this sync.
+#[derive(Debug, Clone)]
+pub struct MappingEntry {
+    pub key: String,
+    pub path: String,
+    pub replace: bool,
+    pub namespaced: bool,
+    pub meta: Option<EmoteMeta>,
+    pub scales: BTreeMap<u32, String>,
+    pub static_path: Option<String>,
+}
+
+impl MappingEntry {
+    fn record(&self) -> MappingRecord {
+        MappingRecord {
+            path: self.path.clone(),
+            scales: self.scales.clone(),
+            static_path: self.static_path.clone(),
+            meta: self.meta.clone(),
+        }
+    }
+}
+
+// Mapping keys only lose the characters file names always did, so existing
+// keys stay stable; file names go through `paths::sanitize_filename`
+fn key_name(name: &str) -> String {
+    let invalid_chars = Regex::new("[<>:/\\|?*]").unwrap();
+    invalid_chars.replace_all(name, "_").into_owned()
+}
+
+// `:Clap:` in channel `xqc` becomes `:xqc/Clap:`
+pub fn namespaced_key(namespace: &str, key: &str) -> String {
+    format!(":{}/{}", key_name(namespace), key.trim_start_matches(':'))
+}
+
+// The channel a key belongs to. One channel has the same namespace on every
+// provider, so the provider is part of it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyOwner {
+    pub provider: String,
+    pub namespace: String,
+}
+
+impl KeyOwner {
+    pub fn new(provider: &str, namespace: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            namespace: namespace.to_string(),
+        }
+    }
+
+    // Who a key from an earlier run belongs to, when its metadata says
+    fn of(record: &MappingRecord) -> Option<Self> {
+        record.meta.as_ref().map(|meta| Self::new(&meta.provider, &meta.channel))
+    }
+}
+
+impl fmt::Display for KeyOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.provider, self.namespace)
+    }
+}
+
+// Two channels wanting the same key
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyCollision {
+    pub key: String,
+    pub namespaced: bool,
+    pub kept: KeyOwner,
+    pub dropped: KeyOwner,
+}
+
+// Every key the channels of a sync want, handed out once all of them are in
+// so the outcome doesn't depend on which files were already on disk. A
+// channel's rank is its position in the priority list (unlisted channels
+// rank after every listed one); on a tie the channel synced first keeps the
+// key.
+#[derive(Debug, Default)]
+pub struct KeyClaims {
+    // Key -> every channel wanting it, in the order they were synced
+    claims: BTreeMap<String, Vec<(KeyOwner, usize, MappingEntry)>>,
+    // Channels that finished this sync, with or without emotes
+    synced: HashSet<KeyOwner>,
+    pub collisions: Vec<KeyCollision>,
+}
+
+impl KeyClaims {
+    // `complete` is false for a channel whose sync was cut short by a
+    // cancel, which may not have claimed everything it still has
+    pub fn add(&mut self, owner: KeyOwner, rank: usize, entries: Vec<MappingEntry>, complete: bool) {
+        for entry in entries {
+            let claims = self.claims.entry(entry.key.clone()).or_default();
+            // A channel listing one name twice keeps its first emote
+            if !claims.iter().any(|(claimant, ..)| *claimant == owner) {
+                claims.push((owner.clone(), rank, entry));
+            }
+        }
+        if complete {
+            self.synced.insert(owner);
+        }
+    }
+
+    // Gives each key to the best ranked channel that wants it. A key from an
+    // earlier run stays with its owner only if that channel wasn't synced
+    // this time and outranks the winner; a synced owner that no longer
+    // claims the key has dropped the emote.
+    pub fn resolve(&mut self, mapping: &mut EmoteMapping, rank: impl Fn(&str) -> usize) {
+        for (key, mut claims) in std::mem::take(&mut self.claims) {
+            // Stable, so ties go to the channel synced first
+            claims.sort_by_key(|(_, rank, _)| *rank);
+            let mut claims = claims.into_iter();
+            let Some((owner, winner_rank, entry)) = claims.next() else {
+                continue;
+            };
+            let collision = |kept: &KeyOwner, dropped: KeyOwner| KeyCollision {
+                key: key.clone(),
+                namespaced: entry.namespaced,
+                kept: kept.clone(),
+                dropped,
+            };
+            for (dropped, ..) in claims {
+                self.collisions.push(collision(&owner, dropped));
+            }
+
+            let take = match mapping.get(&key) {
+                None => true,
+                // Same file, fresher metadata; this is how migrated v1
+                // entries pick up their metadata
+                Some(record) if record.path == entry.path => true,
+                Some(record) => match KeyOwner::of(record) {
+                    Some(previous) if previous == owner || self.synced.contains(&previous) => true,
+                    Some(previous) => {
+                        let previous_rank = rank(&previous.namespace);
+                        let take = winner_rank < previous_rank || (winner_rank == previous_rank && entry.replace);
+                        self.collisions.push(if take {
+                            collision(&owner, previous)
+                        } else {
+                            collision(&previous, owner.clone())
+                        });
+                        take
+                    }
+                    // Migrated v1 entries don't say whose they are
+                    None => entry.replace,
+                },
+            };
+            if take {
+                mapping.insert(key.clone(), entry.record());
+            }
+        }
+    }
+}
+
+// Which names an emote can be triggered with. On 7TV a channel may alias an
+// emote, e.g. call `peepoHappy` `happy`; people who know it from elsewhere
+// look for the original name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmoteNames {
+    // The channel's name for it, as the sync always used
+    #[default]
+    Alias,
+    Original,
+    Both,
+}
+
+impl EmoteNames {
+    // Mapping keys for `emote`, the channel's name first
+    pub fn keys(&self, emote: &ProviderEmote) -> Vec<String> {
+        let alias = emote.code.as_str();
+        let original = emote.original_name.as_deref().unwrap_or(alias);
+        let mut names = match self {
+            EmoteNames::Alias => vec![alias],
+            EmoteNames::Original => vec![original],
+            EmoteNames::Both => vec![alias, original],
+        };
+        names.dedup();
+        names.into_iter().map(|name| format!(":{}:", key_name(name))).collect()
+    }
+}
+
+// Drops every key that points at one of `paths`
+pub fn remove_paths(mapping: &mut EmoteMapping, paths: &[String]) {
+    if !paths.is_empty() {
+        mapping.retain(|_, record| !paths.contains(&record.path));
+        for record in mapping.values_mut() {
+            record.scales.retain(|_, path| !paths.contains(path));
+            if record.static_path.as_ref().is_some_and(|path| paths.contains(path)) {
+                record.static_path = None;
+            }
+        }
+    }
+}
+
+// The v2 mapping sits next to the v1 file, which is still written for the
+// extension and the python scripts
+fn v2_path(mapping_file_path: &Path) -> PathBuf {
+    mapping_file_path.with_file_name(MAPPING_V2_FILE)
+}
+
+// Reads a mapping file, falling back to its `.bak` when a crash left it
+// truncated or otherwise unparseable
+fn read_mapping_file(window: &Window<Wry>, path: &Path) -> Result<Option<String>, CommandError> {
+    Ok(match read_json_with_recovery(path)? {
+        JsonRead::Missing => None,
+        JsonRead::Ok(content) => Some(content),
+        JsonRead::Restored(content) => {
+            emit_log(window, format!("Backend: {} was damaged, restored it from its backup.", path.display()));
+            Some(content)
+        }
+        JsonRead::Corrupt => return Err(CommandError::CorruptMapping(path.display().to_string())),
+    })
+}
+
+fn read_v1(window: &Window<Wry>, mapping_file_path: &Path) -> Result<Option<HashMap<String, String>>, CommandError> {
+    let Some(content) = read_mapping_file(window, mapping_file_path)? else {
+        return Ok(None);
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| CommandError::CorruptMapping(format!("{}: {}", mapping_file_path.display(), e)))
+}
+
+fn read_v2(window: &Window<Wry>, mapping_file_path: &Path) -> Result<Option<EmoteMapping>, CommandError> {
+    let content = match read_mapping_file(window, &v2_path(mapping_file_path)) {
+        Ok(Some(content)) => content,
+        Ok(None) => return Ok(None),
+        Err(CommandError::CorruptMapping(path)) => {
+            emit_log(window, format!("Backend: {} is damaged with no usable backup, rebuilding from v1.", path));
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+    match serde_json::from_str::<MappingFileV2>(&content) {
+        Ok(file) if file.version == MAPPING_VERSION => Ok(Some(file.emotes)),
+        Ok(file) => {
+            emit_log(window, format!("Backend: Unsupported mapping version {}, rebuilding from v1.", file.version));
+            Ok(None)
+        }
+        Err(e) => {
+            emit_log(window, format!("Backend: Error parsing v2 mapping JSON, rebuilding from v1: {}", e));
+            Ok(None)
+        }
+    }
+}
+
+// Loads the mapping, starting fresh when it is missing. The flat v1 file
+// decides which keys exist and where they point, since other tools may have
+// edited it; the v2 file only contributes metadata. Without a v2 file the v1
+// entries are migrated as they are, and gain metadata the next time their
+// channel syncs. A damaged v1 file with no usable backup falls back to the v2
+// records, and fails the load when there are none, rather than being saved
+// over with an empty mapping.
+pub fn load_mapping(window: &Window<Wry>, mapping_file_path: &Path) -> Result<EmoteMapping, CommandError> {
+    let v2 = read_v2(window, mapping_file_path)?;
+    let (v1, mut v2) = match (read_v1(window, mapping_file_path), v2) {
+        (Err(CommandError::CorruptMapping(reason)), Some(v2)) => {
+            emit_log(window, format!("Backend: {} is damaged, rebuilding it from the v2 mapping.", reason));
+            return Ok(v2);
+        }
+        (Err(e), _) => return Err(e),
+        (Ok(None), None) => {
+            emit_log(window, "Backend: No existing mapping file found, starting fresh.".to_string());
+            return Ok(EmoteMapping::new());
+        }
+        (Ok(None), Some(v2)) => return Ok(v2),
+        (Ok(Some(v1)), None) => {
+            emit_log(window, format!("Backend: Migrating {} mapping entries from v1 to v2.", v1.len()));
+            (v1, EmoteMapping::new())
+        }
+        (Ok(Some(v1)), Some(v2)) => (v1, v2),
+    };
+
+    emit_log(window, "Backend: Mapping file exists, attempting to read...".to_string());
+    Ok(v1
+        .into_iter()
+        .map(|(key, path)| {
+            // With reduce motion on, v1 points at the static frame instead
+            match v2.remove(&key) {
+                Some(record) if record.path == path || record.static_path.as_ref() == Some(&path) => (key, record),
+                _ => (key, MappingRecord {
+                    path,
+                    scales: BTreeMap::new(),
+                    static_path: None,
+                    meta: None,
+                }),
+            }
+        })
+        .collect())
+}
+
+pub fn save_mapping(
+    window: &Window<Wry>,
+    mapping_file_path: &Path,
+    mapping: &EmoteMapping,
+    reduce_motion: bool,
+) -> Result<(), CommandError> {
+    let flat: HashMap<&String, &String> = mapping
+        .iter()
+        .map(|(key, record)| {
+            let path = match &record.static_path {
+                Some(static_path) if reduce_motion => static_path,
+                _ => &record.path,
+            };
+            (key, path)
+        })
+        .collect();
+    write_json_with_backup(mapping_file_path, serde_json::to_string_pretty(&flat)?.as_bytes())?;
+
+    let v2 = MappingFileV2 {
+        version: MAPPING_VERSION,
+        emotes: mapping.clone(),
+    };
+    write_json_with_backup(&v2_path(mapping_file_path), serde_json::to_string_pretty(&v2)?.as_bytes())?;
+    emit_log(window, format!("Backend: Emote mapping saved to {}", mapping_file_path.display()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = ":Clap:";
+
+    fn entry(namespace: &str, replace: bool) -> MappingEntry {
+        MappingEntry {
+            key: KEY.to_string(),
+            path: format!("7tv_emotes/{}/Clap.webp", namespace),
+            replace,
+            namespaced: false,
+            meta: Some(EmoteMeta {
+                id: format!("{}-clap", namespace),
+                provider: "7TV".to_string(),
+                channel: namespace.to_string(),
+                name: "Clap".to_string(),
+                original_name: None,
+                animated: false,
+                width: None,
+                height: None,
+                owner: None,
+                tags: Vec::new(),
+                added_at: None,
+                format: Some("webp".to_string()),
+            }),
+            scales: BTreeMap::new(),
+            static_path: None,
+        }
+    }
+
+    fn owner(namespace: &str) -> KeyOwner {
+        KeyOwner::new("7TV", namespace)
+    }
+
+    // `forsen` first, then `xqc`, every other channel after them
+    fn rank(namespace: &str) -> usize {
+        ["forsen", "xqc"].iter().position(|listed| *listed == namespace).unwrap_or(2)
+    }
+
+    fn key_owner(mapping: &EmoteMapping) -> Option<KeyOwner> {
+        mapping.get(KEY).and_then(KeyOwner::of)
+    }
+
+    fn claim(claims: &mut KeyClaims, namespace: &str, replace: bool) {
+        claims.add(owner(namespace), rank(namespace), vec![entry(namespace, replace)], true);
+    }
+
+    #[test]
+    fn the_best_ranked_channel_gets_the_key() {
+        let mut mapping = EmoteMapping::new();
+        let mut claims = KeyClaims::default();
+        claim(&mut claims, "xqc", true);
+        claim(&mut claims, "forsen", true);
+        claims.resolve(&mut mapping, rank);
+
+        assert_eq!(key_owner(&mapping), Some(owner("forsen")));
+        assert_eq!(claims.collisions.len(), 1);
+        assert_eq!((&claims.collisions[0].kept, &claims.collisions[0].dropped), (&owner("forsen"), &owner("xqc")));
+    }
+
+    #[test]
+    fn ties_go_to_the_channel_synced_first() {
+        let mut mapping = EmoteMapping::new();
+        let mut claims = KeyClaims::default();
+        claim(&mut claims, "pokimane", false);
+        claim(&mut claims, "sodapoppin", true);
+        claims.resolve(&mut mapping, rank);
+
+        assert_eq!(key_owner(&mapping), Some(owner("pokimane")));
+    }
+
+    #[test]
+    fn an_unsynced_owner_keeps_the_key_only_when_it_outranks_the_winner() {
+        let mut mapping = EmoteMapping::from([(KEY.to_string(), entry("forsen", true).record())]);
+        let mut claims = KeyClaims::default();
+        claim(&mut claims, "xqc", true);
+        claims.resolve(&mut mapping, rank);
+        assert_eq!(key_owner(&mapping), Some(owner("forsen")));
+        assert_eq!((&claims.collisions[0].kept, &claims.collisions[0].dropped), (&owner("forsen"), &owner("xqc")));
+
+        let mut mapping = EmoteMapping::from([(KEY.to_string(), entry("pokimane", true).record())]);
+        let mut claims = KeyClaims::default();
+        claim(&mut claims, "xqc", false);
+        claims.resolve(&mut mapping, rank);
+        assert_eq!(key_owner(&mapping), Some(owner("xqc")));
+
+        // Same rank: only a fresh download takes the key over
+        for (replace, expected) in [(false, "pokimane"), (true, "sodapoppin")] {
+            let mut mapping = EmoteMapping::from([(KEY.to_string(), entry("pokimane", true).record())]);
+            let mut claims = KeyClaims::default();
+            claim(&mut claims, "sodapoppin", replace);
+            claims.resolve(&mut mapping, rank);
+            assert_eq!(key_owner(&mapping), Some(owner(expected)));
+        }
+    }
+
+    #[test]
+    fn migrated_records_yield_to_fresh_downloads_and_pick_up_metadata() {
+        let migrated = MappingRecord {
+            path: "7tv_emotes/71092938/Clap.gif".to_string(),
+            scales: BTreeMap::new(),
+            static_path: None,
+            meta: None,
+        };
+        for (replace, taken) in [(false, false), (true, true)] {
+            let mut mapping = EmoteMapping::from([(KEY.to_string(), migrated.clone())]);
+            let mut claims = KeyClaims::default();
+            claim(&mut claims, "xqc", replace);
+            claims.resolve(&mut mapping, rank);
+            assert_eq!(mapping[KEY] != migrated, taken);
+        }
+
+        // Same file: the record just gains its metadata
+        let mut mapping = EmoteMapping::from([(KEY.to_string(), MappingRecord {
+            path: entry("xqc", false).path,
+            ..migrated
+        })]);
+        let mut claims = KeyClaims::default();
+        claim(&mut claims, "xqc", false);
+        claims.resolve(&mut mapping, rank);
+        assert_eq!(key_owner(&mapping), Some(owner("xqc")));
+    }
+
+    #[test]
+    fn a_synced_owner_without_the_emote_gives_the_key_up() {
+        for (complete, expected) in [(true, "xqc"), (false, "forsen")] {
+            let mut mapping = EmoteMapping::from([(KEY.to_string(), entry("forsen", true).record())]);
+            let mut claims = KeyClaims::default();
+            claims.add(owner("forsen"), rank("forsen"), Vec::new(), complete);
+            claim(&mut claims, "xqc", false);
+            claims.resolve(&mut mapping, rank);
+            assert_eq!(key_owner(&mapping), Some(owner(expected)));
+        }
+    }
+}
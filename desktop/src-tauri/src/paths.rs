@@ -0,0 +1,245 @@
+use std::path::{Path, PathBuf};
+
+use crate::CommandError;
+
+// Longest file name we produce, in bytes, leaving room for an extension
+const MAX_NAME_BYTES: usize = 150;
+
+// Longest extension kept by `emote_file_name`
+const MAX_EXTENSION_BYTES: usize = 10;
+
+// Names Windows reserves for devices, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_forbidden_char(c: char) -> bool {
+    c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+}
+
+fn is_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+fn truncate(name: &mut String, max_bytes: usize) {
+    if name.len() > max_bytes {
+        let mut end = max_bytes;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+    }
+}
+
+// Turns an emote name into something every filesystem accepts as a single
+// path component: forbidden and control characters become `_`, trailing dots
+// and spaces are dropped, reserved device names are prefixed, and overly long
+// names are cut. Never returns an empty name, `.` or `..`.
+pub fn sanitize_filename(name: &str) -> String {
+    sanitize_to(name, MAX_NAME_BYTES)
+}
+
+fn sanitize_to(name: &str, max_bytes: usize) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if is_forbidden_char(c) { '_' } else { c })
+        .collect();
+    truncate(&mut sanitized, max_bytes);
+
+    let trimmed = sanitized.trim_end_matches(['.', ' ']);
+    if trimmed.is_empty() {
+        return "_".to_string();
+    }
+    if is_reserved(trimmed) {
+        return format!("_{}", trimmed);
+    }
+    trimmed.to_string()
+}
+
+// `sanitize_filename(name)` with `suffix` appended, cutting the name rather
+// than the suffix when it gets too long. Leaves room for an extension.
+pub fn suffixed_filename(name: &str, suffix: &str) -> String {
+    with_suffix(name, '_', suffix)
+}
+
+// `<name>@<scale>x.<extension>`, the file of an emote at a smaller size
+// stored next to its full-size file
+pub fn scaled_file_name(name: &str, scale: u32, extension: &str) -> String {
+    emote_file_name(&with_suffix(name, '@', &format!("{}x", scale)), extension)
+}
+
+// `<name>@static.<extension>`, the first frame of an animated emote
+pub fn static_file_name(name: &str, extension: &str) -> String {
+    emote_file_name(&with_suffix(name, '@', "static"), extension)
+}
+
+fn with_suffix(name: &str, separator: char, suffix: &str) -> String {
+    let suffix = sanitize_filename(suffix);
+    let name = sanitize_to(name, MAX_NAME_BYTES.saturating_sub(MAX_EXTENSION_BYTES + suffix.len() + 2));
+    format!("{}{}{}", name, separator, suffix)
+}
+
+// `<name>.<extension>` for a downloaded emote. Extensions come from provider
+// responses, so only ASCII letters and digits are kept.
+pub fn emote_file_name(name: &str, extension: &str) -> String {
+    let extension: String = extension.chars().filter(|c| c.is_ascii_alphanumeric()).take(MAX_EXTENSION_BYTES).collect();
+    if extension.is_empty() {
+        return sanitize_filename(name);
+    }
+    // Reserved names stay reserved with an extension, so `name` is checked
+    // on its own
+    let name = sanitize_to(name, MAX_NAME_BYTES - extension.len() - 1);
+    format!("{}.{}", name, extension)
+}
+
+// A path relative to MojifyData the way the mapping and the index store it,
+// with `/` separators on every platform
+pub fn mapping_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+// Lowercase extension of a file name or path, e.g. `webp`
+pub fn file_extension(path: impl AsRef<Path>) -> Option<String> {
+    path.as_ref().extension().map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+// Checks that `component` can be used as is for a single path component.
+// Unlike `sanitize_filename` this never rewrites anything, so two different
+// inputs can't end up in the same folder.
+pub fn validate_component(component: &str) -> Result<&str, CommandError> {
+    if component.is_empty() || component == "." || component == ".." || sanitize_filename(component) != component {
+        return Err(CommandError::UnsafePath(component.to_string()));
+    }
+    Ok(component)
+}
+
+// Joins validated components onto `base`
+pub fn safe_join(base: &Path, components: &[&str]) -> Result<PathBuf, CommandError> {
+    let mut path = base.to_path_buf();
+    for component in components {
+        path.push(validate_component(component)?);
+    }
+    Ok(path)
+}
+
+// Channel ids, logins and slugs only ever use ASCII letters, digits, `_`
+// and `-`, which also keeps them safe as folder names
+pub fn is_valid_channel_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 100 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_replaces_forbidden_characters() {
+        assert_eq!(sanitize_filename("a<b>c:d\"e/f\\g|h?i*j"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(sanitize_filename("tab\there\u{7}"), "tab_here_");
+    }
+
+    #[test]
+    fn sanitize_handles_dot_names() {
+        assert_eq!(sanitize_filename(".."), "_");
+        assert_eq!(sanitize_filename("."), "_");
+        assert_eq!(sanitize_filename(""), "_");
+        assert_eq!(sanitize_filename("   "), "_");
+        assert_eq!(sanitize_filename("../../x"), ".._.._x");
+    }
+
+    #[test]
+    fn sanitize_strips_trailing_dots_and_spaces() {
+        assert_eq!(sanitize_filename("Kappa. . "), "Kappa");
+        assert_eq!(sanitize_filename(" leading stays"), " leading stays");
+    }
+
+    #[test]
+    fn sanitize_prefixes_reserved_names() {
+        assert_eq!(sanitize_filename("CON"), "_CON");
+        assert_eq!(sanitize_filename("nul"), "_nul");
+        assert_eq!(sanitize_filename("com1.gif"), "_com1.gif");
+        assert_eq!(sanitize_filename("CONSOLE"), "CONSOLE");
+        assert_eq!(sanitize_filename("LPT10"), "LPT10");
+    }
+
+    #[test]
+    fn sanitize_truncates_on_char_boundary() {
+        let long = "é".repeat(200);
+        let sanitized = sanitize_filename(&long);
+        assert!(sanitized.len() <= MAX_NAME_BYTES);
+        assert!(sanitized.chars().all(|c| c == 'é'));
+    }
+
+    #[test]
+    fn sanitize_leaves_ordinary_names_alone() {
+        assert_eq!(sanitize_filename("peepoHappy"), "peepoHappy");
+        assert_eq!(sanitize_filename("D:"), "D_");
+    }
+
+    #[test]
+    fn emote_file_names_sanitize_both_parts() {
+        assert_eq!(emote_file_name("Clap", "webp"), "Clap.webp");
+        assert_eq!(emote_file_name("Clap", "../gif"), "Clap.gif");
+        assert_eq!(emote_file_name("CON", "png"), "_CON.png");
+        assert_eq!(emote_file_name("..", "png"), "_.png");
+        assert_eq!(emote_file_name("Clap", "/"), "Clap");
+    }
+
+    #[test]
+    fn long_names_keep_their_extension_and_suffix() {
+        let long = "a".repeat(300);
+        let file_name = emote_file_name(&long, "webp");
+        assert!(file_name.len() <= MAX_NAME_BYTES && file_name.ends_with("a.webp"));
+        let suffixed = emote_file_name(&suffixed_filename(&long, "60ae4ec30e35477634988c18"), "webp");
+        assert!(suffixed.len() <= MAX_NAME_BYTES && suffixed.ends_with("a_60ae4ec30e35477634988c18.webp"));
+        assert_eq!(suffixed_filename("a:b", "01"), "a_b_01");
+        assert_eq!(suffixed_filename("Kappa.", "01"), "Kappa_01");
+        let scaled = scaled_file_name(&long, 1, "webp");
+        assert!(scaled.len() <= MAX_NAME_BYTES && scaled.ends_with("a@1x.webp"));
+        assert_eq!(scaled_file_name("Clap", 2, "gif"), "Clap@2x.gif");
+        assert_eq!(static_file_name("Clap", "png"), "Clap@static.png");
+    }
+
+    #[test]
+    fn mapping_paths_use_forward_slashes() {
+        assert_eq!(mapping_path(&Path::new("7tv_emotes").join("xqc").join("Clap.webp")), "7tv_emotes/xqc/Clap.webp");
+        assert_eq!(file_extension("7tv_emotes/xqc/Clap.WebP").as_deref(), Some("webp"));
+        assert_eq!(file_extension("7tv_emotes/xqc/Clap"), None);
+    }
+
+    #[test]
+    fn validate_rejects_unsafe_components() {
+        for unsafe_component in ["", ".", "..", "../x", "a/b", "a\\b", "CON", "name.", "x\0y"] {
+            assert!(
+                matches!(validate_component(unsafe_component), Err(CommandError::UnsafePath(_))),
+                "{:?} should be rejected",
+                unsafe_component
+            );
+        }
+        assert_eq!(validate_component("7tv_emotes").unwrap(), "7tv_emotes");
+    }
+
+    #[test]
+    fn safe_join_stays_under_base() {
+        let base = Path::new("MojifyData");
+        assert_eq!(
+            safe_join(base, &["7tv_emotes", "12345"]).unwrap(),
+            base.join("7tv_emotes").join("12345")
+        );
+        assert!(safe_join(base, &["7tv_emotes", "../../x"]).is_err());
+        assert!(safe_join(base, &["..", "x"]).is_err());
+    }
+
+    #[test]
+    fn channel_ids_are_restricted() {
+        assert!(is_valid_channel_id("xqc"));
+        assert!(is_valid_channel_id("71092938"));
+        assert!(is_valid_channel_id("UC-lHJZR3Gqxm24_Vd_AJ5Yw"));
+        assert!(!is_valid_channel_id("../../x"));
+        assert!(!is_valid_channel_id("a b"));
+        assert!(!is_valid_channel_id(""));
+        assert!(!is_valid_channel_id(&"a".repeat(101)));
+    }
+}
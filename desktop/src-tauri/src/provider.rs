@@ -0,0 +1,277 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::http::HttpClient;
+use crate::paths::is_valid_channel_id;
+use crate::CommandError;
+
+// Streaming platforms 7TV links accounts for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Platform {
+    Twitch,
+    YouTube,
+    Kick,
+}
+
+impl Platform {
+    // Name used in API paths and channel prefixes
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Twitch => "twitch",
+            Platform::YouTube => "youtube",
+            Platform::Kick => "kick",
+        }
+    }
+}
+
+// One entry of the channel list: a platform account id, optionally prefixed
+// with its platform (`kick:somestreamer`, `youtube:UC...`). Unprefixed
+// entries are Twitch ids or logins. Twitch logins and Kick channel names are
+// resolved to numeric user ids before syncing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelInput {
+    pub platform: Platform,
+    pub id: String,
+    // Twitch login or Kick channel name the id was resolved from, if the
+    // user entered one
+    pub login: Option<String>,
+}
+
+impl ChannelInput {
+    pub fn parse(input: &str) -> Result<Self, CommandError> {
+        let (platform, id) = match input.split_once(':') {
+            Some((prefix, id)) => {
+                let platform = match prefix.trim().to_lowercase().as_str() {
+                    "twitch" => Platform::Twitch,
+                    "youtube" => Platform::YouTube,
+                    "kick" => Platform::Kick,
+                    other => return Err(CommandError::UnsupportedPlatform(other.to_string())),
+                };
+                (platform, id.trim())
+            }
+            None => (Platform::Twitch, input.trim()),
+        };
+        if id.is_empty() {
+            return Err(CommandError::InvalidChannelIds);
+        }
+        // The id ends up as a folder name, so `..` or separators never get
+        // past this point
+        if !is_valid_channel_id(id) {
+            return Err(CommandError::UnsafePath(id.to_string()));
+        }
+        Ok(Self {
+            platform,
+            id: id.to_string(),
+            login: None,
+        })
+    }
+
+    // Replaces a Twitch login or Kick channel name with its resolved numeric
+    // id, keeping the name around for summaries
+    pub fn with_user_id(self, user_id: String) -> Self {
+        Self {
+            platform: self.platform,
+            login: Some(self.id),
+            id: user_id,
+        }
+    }
+
+    // Folder name for the channel. Twitch keeps the bare id so existing
+    // downloads stay where they are.
+    pub fn dir_name(&self) -> String {
+        match self.platform {
+            Platform::Twitch => self.id.clone(),
+            platform => format!("{}_{}", platform.as_str(), self.id),
+        }
+    }
+
+    // Namespace for the channel's mapping keys: the Twitch login when we know
+    // it, otherwise the folder name
+    pub fn namespace(&self) -> String {
+        match (self.platform, &self.login) {
+            (Platform::Twitch, Some(login)) => login.to_lowercase(),
+            _ => self.dir_name(),
+        }
+    }
+}
+
+impl fmt::Display for ChannelInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.platform, &self.login) {
+            (Platform::Twitch, Some(login)) => write!(f, "{} ({})", login, self.id),
+            (Platform::Twitch, None) => write!(f, "{}", self.id),
+            (platform, Some(login)) => write!(f, "{}:{} ({})", platform.as_str(), login, self.id),
+            (platform, None) => write!(f, "{}:{}", platform.as_str(), self.id),
+        }
+    }
+}
+
+// What a provider needs to fetch the emotes of a resolved channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum EmoteSource {
+    // A user/channel id on the provider's API
+    Channel(String),
+    // A provider-side emote set id
+    EmoteSet(String),
+    // The provider's global emotes
+    Global,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedChannel {
+    pub source: EmoteSource,
+    // Folder name under the provider's emote directory
+    pub dir_name: String,
+    // Human readable name used in logs and summaries
+    pub label: String,
+    // Prefix for the channel's namespaced mapping keys, e.g. `xqc` in
+    // `:xqc/Clap:`
+    pub namespace: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EmoteOwner {
+    pub id: String,
+    pub username: String,
+    pub display_name: String,
+}
+
+// Image formats providers serve emotes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Webp,
+    Avif,
+    Gif,
+    Png,
+}
+
+impl ImageFormat {
+    // Lowercase file extension, as in `EmoteFile::format`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Webp => "webp",
+            ImageFormat::Avif => "avif",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Png => "png",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [ImageFormat::Webp, ImageFormat::Avif, ImageFormat::Gif, ImageFormat::Png]
+            .into_iter()
+            .find(|format| name.eq_ignore_ascii_case(format.extension()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmoteFile {
+    // Provider file name, e.g. "4x.gif"
+    pub name: String,
+    pub url: String,
+    // Lowercase file extension, e.g. "gif"
+    pub format: String,
+    pub scale: u32,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // First frame of an animated file, when the provider serves one
+    pub static_file: Option<StaticFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StaticFile {
+    pub url: String,
+    // Lowercase file extension, e.g. "png"
+    pub format: String,
+}
+
+// Provider-independent view of a single emote
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderEmote {
+    pub id: String,
+    // The name the channel uses, which on 7TV may be an alias
+    pub code: String,
+    // The emote's own name when the provider distinguishes it from `code`
+    pub original_name: Option<String>,
+    pub animated: bool,
+    pub files: Vec<EmoteFile>,
+    pub owner: Option<EmoteOwner>,
+    pub tags: Vec<String>,
+    // When the channel added the emote, as a Unix timestamp in milliseconds
+    pub added_at: Option<u64>,
+}
+
+// A URL to try for an emote and the extension to store it under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadCandidate {
+    pub url: String,
+    pub extension: String,
+}
+
+pub trait EmoteProvider: Send + Sync {
+    // Short name used in logs and summaries, e.g. "7TV"
+    fn name(&self) -> &'static str;
+
+    // Folder under MojifyData holding this provider's emotes
+    fn folder(&self) -> &'static str;
+
+    // Turns user input into a channel this provider can list, or `None` when
+    // the channel has no account on the provider.
+    fn resolve_channel<'a>(
+        &'a self,
+        client: &'a HttpClient,
+        input: &'a ChannelInput,
+    ) -> BoxFuture<'a, Result<Option<ResolvedChannel>, CommandError>>;
+
+    fn list_emotes<'a>(
+        &'a self,
+        client: &'a HttpClient,
+        channel: &'a ResolvedChannel,
+    ) -> BoxFuture<'a, Result<Vec<ProviderEmote>, CommandError>>;
+
+    // URLs to try for an emote, most preferred first
+    fn download_candidates(&self, emote: &ProviderEmote) -> Vec<DownloadCandidate>;
+
+    // Emotes every user of the provider sees, synced once per run
+    fn global_channel(&self) -> Option<ResolvedChannel> {
+        None
+    }
+}
+
+// Reads the leading scale out of file names like "4x.gif"
+pub fn scale_from_name(name: &str) -> u32 {
+    name.chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>()
+        .parse()
+        .unwrap_or(1)
+}
+
+// Some APIs hand out protocol-relative URLs (`//cdn.frankerfacez.com/...`,
+// `//cdn.7tv.app/emote/<id>`)
+pub fn absolute_url(url: &str) -> String {
+    if url.starts_with("//") {
+        format!("https:{}", url)
+    } else {
+        url.to_string()
+    }
+}
+
+// Shared by providers whose APIs return 404 for unknown users
+pub async fn get_json<T: serde::de::DeserializeOwned>(
+    client: &HttpClient,
+    url: &str,
+) -> Result<Option<T>, CommandError> {
+    let response = client.get(url).await?;
+    if response.status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !response.status.is_success() {
+        return Err(CommandError::HttpStatus {
+            status: response.status.as_u16(),
+            url: url.to_string(),
+        });
+    }
+    Ok(Some(serde_json::from_slice(&response.body)?))
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::paths::mapping_path;
+
+const ARCHIVE_DIR: &str = "archive";
+
+// What happens to files of emotes a channel no longer has
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneMode {
+    // Keep them around, as the sync always used to
+    #[default]
+    Off,
+    Delete,
+    // Move them to `archive/<provider folder>/<channel dir>`
+    Archive,
+}
+
+// Removes or archives every file in `<mojify_data_dir>/<folder>` whose name
+// isn't in `keep`, returning the pruned files' paths relative to the data
+// dir (the form used in the mapping).
+pub fn prune_folder(
+    mojify_data_dir: &Path,
+    folder: &Path,
+    keep: &HashSet<String>,
+    mode: PruneMode,
+) -> io::Result<Vec<String>> {
+    let mut pruned = Vec::new();
+    if mode == PruneMode::Off {
+        return Ok(pruned);
+    }
+
+    let channel_dir = mojify_data_dir.join(folder);
+    let archive_dir = mojify_data_dir.join(ARCHIVE_DIR).join(folder);
+    for entry in fs::read_dir(&channel_dir)? {
+        let entry = entry?;
+        let filename = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_file() || keep.contains(&filename) {
+            continue;
+        }
+        match mode {
+            PruneMode::Off | PruneMode::Delete => fs::remove_file(entry.path())?,
+            PruneMode::Archive => {
+                fs::create_dir_all(&archive_dir)?;
+                let archived = archive_dir.join(&filename);
+                // `rename` won't replace an existing file on Windows
+                if archived.exists() {
+                    fs::remove_file(&archived)?;
+                }
+                fs::rename(entry.path(), archived)?;
+            }
+        }
+        pruned.push(mapping_path(&folder.join(&filename)));
+    }
+    Ok(pruned)
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::events::ChannelStatus;
+use crate::jobs::JobId;
+use crate::sync::{ChannelStats, SyncTotals};
+use crate::CommandError;
+
+// Why an emote or a whole channel failed, coarse enough for the frontend to
+// group on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FailureReason {
+    HttpStatus { status: u16 },
+    // Connection, TLS or timeout errors
+    Request,
+    Io,
+    // A response that couldn't be parsed
+    Decode,
+    // The provider listed the emote without any file we can download
+    NoFile,
+    Other,
+}
+
+impl From<&CommandError> for FailureReason {
+    fn from(error: &CommandError) -> Self {
+        match error {
+            CommandError::HttpStatus { status, .. } => FailureReason::HttpStatus { status: *status },
+            CommandError::Request(_) => FailureReason::Request,
+            CommandError::Io(_) => FailureReason::Io,
+            CommandError::Json(_) => FailureReason::Decode,
+            _ => FailureReason::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Failure {
+    pub reason: FailureReason,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(reason: FailureReason, message: impl Into<String>) -> Self {
+        Self {
+            reason,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<&CommandError> for Failure {
+    fn from(error: &CommandError) -> Self {
+        Failure::new(error.into(), error.to_string())
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmoteFailure {
+    pub emote_id: String,
+    pub emote_code: String,
+    #[serde(flatten)]
+    pub failure: Failure,
+}
+
+impl fmt::Display for EmoteFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.emote_code, self.failure)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelReport {
+    pub provider: String,
+    pub channel: String,
+    pub status: ChannelStatus,
+    #[serde(flatten)]
+    pub stats: ChannelStats,
+    // Set when the channel couldn't be resolved or listed at all
+    pub error: Option<Failure>,
+}
+
+// Outcome of one sync job, returned to the frontend and kept in the history
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    pub job_id: JobId,
+    pub description: String,
+    // Unix timestamp, in seconds
+    pub started_at: u64,
+    pub elapsed_ms: u64,
+    pub cancelled: bool,
+    #[serde(flatten)]
+    pub totals: SyncTotals,
+    pub channels: Vec<ChannelReport>,
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::http::RetryPolicy;
+use crate::mapping::EmoteNames;
+use crate::provider::ImageFormat;
+use crate::prune::PruneMode;
+use crate::seventv::SEVENTV_DEFAULT_FORMATS;
+use crate::CommandError;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+// User preferences persisted next to the mapping in MojifyData. Missing
+// fields fall back to their defaults so older files keep loading.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    // Also sync the 7TV global emote set into `7tv_emotes/global`
+    pub include_7tv_globals: bool,
+    // Endpoint used to turn Twitch logins into ids, with `{login}` as a
+    // placeholder. Falls back to the 7TV user search when unset.
+    pub twitch_lookup_url: Option<String>,
+    // Emote file downloads in flight against any single CDN host. API
+    // requests don't count against it.
+    pub max_downloads_per_host: usize,
+    // Emotes of one channel processed side by side
+    pub max_parallel_emotes: usize,
+    // Channels synced side by side
+    pub max_parallel_channels: usize,
+    // Backoff for transient API and CDN failures
+    pub retry: RetryPolicy,
+    // Also emit the free-text `download://log` stream next to the typed
+    // `sync://` progress events
+    pub text_log: bool,
+    // Delete or archive files of emotes a channel has dropped
+    pub prune_mode: PruneMode,
+    // Whether 7TV emotes are keyed by the channel's alias, their original
+    // name, or both
+    pub emote_names: EmoteNames,
+    // Channel namespaces (Twitch login, or the folder name for other
+    // platforms and sets) in the order they get bare `:name:` keys when
+    // channels collide. Unlisted channels come after, in sync order.
+    pub channel_priority: Vec<String>,
+    // 7TV formats to download, most preferred first. Emotes advertising
+    // none of them are reported as failed. Files already downloaded in a
+    // less preferred format are replaced on the next sync.
+    pub format_preference: Vec<ImageFormat>,
+    // Smaller sizes (1-4) to store next to each emote's full-size file,
+    // e.g. `[1]` for suggestion thumbnails. Sizes a provider doesn't serve
+    // are skipped.
+    pub scales: Vec<u32>,
+    // Point the v1 mapping, which the extension and scripts look emotes up
+    // in, at the static first frame of animated emotes. Changing it rewrites
+    // the v1 mapping right away, or when a running sync writes it.
+    pub reduce_motion: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            include_7tv_globals: false,
+            twitch_lookup_url: None,
+            max_downloads_per_host: 8,
+            max_parallel_emotes: 8,
+            max_parallel_channels: 4,
+            retry: RetryPolicy::default(),
+            text_log: true,
+            prune_mode: PruneMode::Off,
+            emote_names: EmoteNames::Alias,
+            channel_priority: Vec::new(),
+            format_preference: SEVENTV_DEFAULT_FORMATS.to_vec(),
+            scales: Vec::new(),
+            reduce_motion: false,
+        }
+    }
+}
+
+pub fn load_settings(mojify_data_dir: &Path) -> Result<Settings, CommandError> {
+    let path = mojify_data_dir.join(SETTINGS_FILE);
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn save_settings(mojify_data_dir: &Path, settings: &Settings) -> Result<(), CommandError> {
+    fs::create_dir_all(mojify_data_dir)?;
+    fs::write(mojify_data_dir.join(SETTINGS_FILE), serde_json::to_string_pretty(settings)?)?;
+    Ok(())
+}
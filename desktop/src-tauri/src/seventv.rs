@@ -0,0 +1,326 @@
+use futures::future::{BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::provider::{
+    absolute_url, get_json, scale_from_name, ChannelInput, DownloadCandidate, EmoteFile, EmoteOwner, EmoteProvider,
+    EmoteSource, ImageFormat, Platform, ProviderEmote, ResolvedChannel, StaticFile,
+};
+use crate::http::HttpClient;
+use crate::CommandError;
+
+pub const SEVENTV_API_BASE: &str = "https://7tv.io/v3";
+pub const SEVENTV_CDN_BASE: &str = "https://cdn.7tv.app";
+
+// The GIF when there is one, otherwise the PNG, as the sync always picked,
+// with the other formats as a last resort
+pub const SEVENTV_DEFAULT_FORMATS: [ImageFormat; 4] =
+    [ImageFormat::Gif, ImageFormat::Png, ImageFormat::Webp, ImageFormat::Avif];
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvEmoteFile {
+    name: String,
+    static_name: String,
+    width: u32,
+    height: u32,
+    frame_count: u32,
+    size: u64,
+    format: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvEmoteHost {
+    url: String,
+    files: Vec<SevenTvEmoteFile>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvEmoteOwnerConnection {
+    id: String,
+    platform: String,
+    username: String,
+    display_name: String,
+    linked_at: u64,
+    emote_capacity: u32,
+    emote_set_id: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SevenTvEmoteOwnerStyle {
+    #[serde(flatten)]
+    #[serde(default)]
+    extra: HashMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvEmoteOwner {
+    id: String,
+    username: String,
+    display_name: String,
+    avatar_url: Option<String>,
+    #[serde(default)]
+    style: SevenTvEmoteOwnerStyle,
+    role_ids: Option<Vec<String>>,
+    #[serde(default)]
+    connections: Vec<SevenTvEmoteOwnerConnection>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvEmoteData {
+    id: String,
+    name: String,
+    flags: u32,
+    tags: Option<Vec<String>>,
+    lifecycle: u32,
+    state: Vec<String>,
+    listed: bool,
+    animated: bool,
+    owner: Option<SevenTvEmoteOwner>,
+    host: SevenTvEmoteHost,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvEmote {
+    id: String,
+    name: String,
+    flags: u32,
+    timestamp: u64,
+    actor_id: Option<String>,
+    data: SevenTvEmoteData,
+    origin_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvEmoteSet {
+    id: String,
+    name: String,
+    flags: u32,
+    tags: Option<Vec<String>>,
+    immutable: bool,
+    privileged: bool,
+    emotes: Vec<SevenTvEmote>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvUserResponse {
+    id: String,
+    platform: String,
+    username: String,
+    display_name: String,
+    linked_at: u64,
+    emote_capacity: u32,
+    emote_set_id: Option<String>,
+    emote_set: Option<SevenTvEmoteSet>,
+}
+
+// We might need a struct for user details if we fetch that part
+// #[derive(Deserialize, Debug)]
+// struct SevenTvUser {
+//     id: String,
+//     username: String,
+//     display_name: String,
+// }
+
+#[derive(Debug, Clone)]
+pub struct SevenTvProvider {
+    api_base: String,
+    cdn_base: String,
+    include_globals: bool,
+    formats: Vec<ImageFormat>,
+    // Emote sets that came along with a user lookup, so `list_emotes` doesn't
+    // fetch them a second time
+    prefetched: Arc<Mutex<HashMap<String, SevenTvEmoteSet>>>,
+}
+
+impl Default for SevenTvProvider {
+    fn default() -> Self {
+        Self::with_base_urls(SEVENTV_API_BASE, SEVENTV_CDN_BASE)
+    }
+}
+
+impl SevenTvProvider {
+    pub fn with_base_urls(api_base: &str, cdn_base: &str) -> Self {
+        Self {
+            api_base: api_base.trim_end_matches('/').to_string(),
+            cdn_base: cdn_base.trim_end_matches('/').to_string(),
+            include_globals: false,
+            formats: SEVENTV_DEFAULT_FORMATS.to_vec(),
+            prefetched: Arc::default(),
+        }
+    }
+
+    // Opts into syncing the global emote set every 7TV user sees
+    pub fn with_globals(mut self, include_globals: bool) -> Self {
+        self.include_globals = include_globals;
+        self
+    }
+
+    // Formats to pick from the files an emote advertises, most preferred
+    // first. An empty list keeps the defaults.
+    pub fn with_formats(mut self, formats: Vec<ImageFormat>) -> Self {
+        if !formats.is_empty() {
+            self.formats = formats;
+        }
+        self
+    }
+
+    pub fn user_url(&self, platform: Platform, id: &str) -> String {
+        format!("{}/users/{}/{}", self.api_base, platform.as_str(), id)
+    }
+
+    pub fn emote_set_url(&self, set_id: &str) -> String {
+        format!("{}/emote-sets/{}", self.api_base, set_id)
+    }
+
+    // A standalone emote set, stored under its own `set_<id>` folder so it
+    // never mixes with a channel's files
+    pub fn emote_set_channel(&self, set_id: &str) -> ResolvedChannel {
+        ResolvedChannel {
+            source: EmoteSource::EmoteSet(set_id.to_string()),
+            dir_name: format!("set_{}", set_id),
+            label: format!("set {}", set_id),
+            namespace: format!("set_{}", set_id),
+        }
+    }
+
+    fn host_url(&self, emote: &SevenTvEmote) -> String {
+        let url = emote.data.host.url.trim_end_matches('/');
+        if url.is_empty() {
+            format!("{}/emote/{}", self.cdn_base, emote.id)
+        } else {
+            absolute_url(url)
+        }
+    }
+
+    fn normalize(&self, emote: &SevenTvEmote) -> ProviderEmote {
+        let host_url = self.host_url(emote);
+        let files = emote
+            .data
+            .host
+            .files
+            .iter()
+            .map(|file| EmoteFile {
+                name: file.name.clone(),
+                url: format!("{}/{}", host_url, file.name),
+                format: file.format.to_lowercase(),
+                scale: scale_from_name(&file.name),
+                width: Some(file.width),
+                height: Some(file.height),
+                // Static variants come in the format of the file they belong to
+                static_file: (!file.static_name.is_empty() && file.static_name != file.name).then(|| StaticFile {
+                    url: format!("{}/{}", host_url, file.static_name),
+                    format: file.format.to_lowercase(),
+                }),
+            })
+            .collect();
+        let owner = emote.data.owner.as_ref().map(|owner| EmoteOwner {
+            id: owner.id.clone(),
+            username: owner.username.clone(),
+            display_name: owner.display_name.clone(),
+        });
+
+        ProviderEmote {
+            id: emote.id.clone(),
+            code: emote.name.clone(),
+            original_name: Some(emote.data.name.clone()),
+            animated: emote.data.animated,
+            files,
+            owner,
+            tags: emote.data.tags.clone().unwrap_or_default(),
+            added_at: Some(emote.timestamp),
+        }
+    }
+}
+
+impl EmoteProvider for SevenTvProvider {
+    fn name(&self) -> &'static str {
+        "7TV"
+    }
+
+    fn folder(&self) -> &'static str {
+        "7tv_emotes"
+    }
+
+    fn resolve_channel<'a>(
+        &'a self,
+        client: &'a HttpClient,
+        input: &'a ChannelInput,
+    ) -> BoxFuture<'a, Result<Option<ResolvedChannel>, CommandError>> {
+        async move {
+            let url = self.user_url(input.platform, &input.id);
+            let Some(user) = get_json::<SevenTvUserResponse>(client, &url).await? else {
+                return Ok(None);
+            };
+            let set_id = user.emote_set_id.or(user.emote_set.as_ref().map(|set| set.id.clone()));
+            if let Some(set) = user.emote_set.filter(|set| Some(&set.id) == set_id.as_ref()) {
+                self.prefetched.lock().unwrap_or_else(|e| e.into_inner()).insert(set.id.clone(), set);
+            }
+            Ok(set_id.map(|set_id| ResolvedChannel {
+                source: EmoteSource::EmoteSet(set_id),
+                dir_name: input.dir_name(),
+                label: input.to_string(),
+                namespace: input.namespace(),
+            }))
+        }
+        .boxed()
+    }
+
+    fn list_emotes<'a>(
+        &'a self,
+        client: &'a HttpClient,
+        channel: &'a ResolvedChannel,
+    ) -> BoxFuture<'a, Result<Vec<ProviderEmote>, CommandError>> {
+        async move {
+            let set_id = match &channel.source {
+                EmoteSource::EmoteSet(set_id) => set_id.as_str(),
+                EmoteSource::Global => "global",
+                // 7TV channels always resolve to their emote set
+                EmoteSource::Channel(_) => return Ok(Vec::new()),
+            };
+            let prefetched = self.prefetched.lock().unwrap_or_else(|e| e.into_inner()).remove(set_id);
+            let emote_set = match prefetched {
+                Some(set) => set,
+                // A mistyped or deleted set is an error, not an empty set
+                None => {
+                    let url = self.emote_set_url(set_id);
+                    get_json::<SevenTvEmoteSet>(client, &url)
+                        .await?
+                        .ok_or(CommandError::HttpStatus { status: 404, url })?
+                }
+            };
+            Ok(emote_set.emotes.iter().map(|emote| self.normalize(emote)).collect())
+        }
+        .boxed()
+    }
+
+    fn download_candidates(&self, emote: &ProviderEmote) -> Vec<DownloadCandidate> {
+        // The largest advertised files, in the order of the format preference
+        let Some(scale) = emote.files.iter().map(|file| file.scale).max() else {
+            return Vec::new();
+        };
+        self.formats
+            .iter()
+            .filter_map(|format| {
+                emote
+                    .files
+                    .iter()
+                    .find(|file| file.scale == scale && ImageFormat::parse(&file.format) == Some(*format))
+            })
+            .map(|file| DownloadCandidate {
+                url: file.url.clone(),
+                extension: file.format.clone(),
+            })
+            .collect()
+    }
+
+    fn global_channel(&self) -> Option<ResolvedChannel> {
+        self.include_globals.then(|| ResolvedChannel {
+            source: EmoteSource::Global,
+            dir_name: "global".to_string(),
+            label: "global".to_string(),
+            namespace: "7tv_global".to_string(),
+        })
+    }
+}
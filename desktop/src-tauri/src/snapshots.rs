@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::paths::{safe_join, validate_component};
+use crate::provider::{ProviderEmote, ResolvedChannel};
+use crate::CommandError;
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+// Snapshots kept per channel; older ones are dropped
+const MAX_SNAPSHOTS_PER_CHANNEL: usize = 20;
+
+// The emotes a channel had at the end of one sync, keyed by provider emote id
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelSnapshot {
+    // Unix timestamp, in seconds
+    pub taken_at: u64,
+    pub provider: String,
+    pub channel: String,
+    pub emotes: BTreeMap<String, String>,
+}
+
+impl ChannelSnapshot {
+    pub fn new(taken_at: u64, provider: &str, channel: &ResolvedChannel, emotes: &[ProviderEmote]) -> Self {
+        Self {
+            taken_at,
+            provider: provider.to_string(),
+            channel: channel.label.clone(),
+            emotes: emotes.iter().map(|emote| (emote.id.clone(), emote.code.clone())).collect(),
+        }
+    }
+}
+
+// A channel with recorded snapshots, as passed back to `diff_snapshots`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotChannel {
+    pub folder: String,
+    pub dir_name: String,
+    pub provider: String,
+    pub channel: String,
+    pub snapshots: usize,
+    pub latest: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotEmote {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedEmote {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    pub provider: String,
+    pub channel: String,
+    pub from: u64,
+    pub to: u64,
+    pub added: Vec<SnapshotEmote>,
+    pub removed: Vec<SnapshotEmote>,
+    pub renamed: Vec<RenamedEmote>,
+}
+
+// `folder` and `dir_name` come from the frontend, so anything that isn't a
+// single safe path component is rejected rather than joined
+fn snapshot_path(mojify_data_dir: &Path, folder: &str, dir_name: &str) -> Result<PathBuf, CommandError> {
+    validate_component(dir_name)?;
+    safe_join(&mojify_data_dir.join(SNAPSHOTS_DIR), &[folder, &format!("{}.json", dir_name)])
+}
+
+fn read_snapshots(path: &Path) -> Vec<ChannelSnapshot> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn load_snapshots(mojify_data_dir: &Path, folder: &str, dir_name: &str) -> Result<Vec<ChannelSnapshot>, CommandError> {
+    Ok(read_snapshots(&snapshot_path(mojify_data_dir, folder, dir_name)?))
+}
+
+pub fn record_snapshot(
+    mojify_data_dir: &Path,
+    folder: &str,
+    dir_name: &str,
+    snapshot: ChannelSnapshot,
+) -> Result<(), CommandError> {
+    let path = snapshot_path(mojify_data_dir, folder, dir_name)?;
+    let mut snapshots = read_snapshots(&path);
+    snapshots.push(snapshot);
+    let excess = snapshots.len().saturating_sub(MAX_SNAPSHOTS_PER_CHANNEL);
+    snapshots.drain(..excess);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(&snapshots)?)?;
+    Ok(())
+}
+
+pub fn list_snapshot_channels(mojify_data_dir: &Path) -> Result<Vec<SnapshotChannel>, CommandError> {
+    let root = mojify_data_dir.join(SNAPSHOTS_DIR);
+    let mut channels = Vec::new();
+    if !root.exists() {
+        return Ok(channels);
+    }
+    for folder in fs::read_dir(&root)? {
+        let folder = folder?;
+        if !folder.file_type()?.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(folder.path())? {
+            let path = file?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let snapshots = read_snapshots(&path);
+            let Some(latest) = snapshots.last() else {
+                continue;
+            };
+            channels.push(SnapshotChannel {
+                folder: folder.file_name().to_string_lossy().into_owned(),
+                dir_name: path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                provider: latest.provider.clone(),
+                channel: latest.channel.clone(),
+                snapshots: snapshots.len(),
+                latest: latest.taken_at,
+            });
+        }
+    }
+    channels.sort_by(|a, b| (&a.folder, &a.dir_name).cmp(&(&b.folder, &b.dir_name)));
+    Ok(channels)
+}
+
+// Emotes whose id only shows up in `to` were added, ids only in `from` were
+// removed, and ids in both under a different name were renamed
+pub fn diff_snapshots(from: &ChannelSnapshot, to: &ChannelSnapshot) -> SnapshotDiff {
+    let emote = |(id, name): (&String, &String)| SnapshotEmote {
+        id: id.clone(),
+        name: name.clone(),
+    };
+    SnapshotDiff {
+        provider: to.provider.clone(),
+        channel: to.channel.clone(),
+        from: from.taken_at,
+        to: to.taken_at,
+        added: to.emotes.iter().filter(|(id, _)| !from.emotes.contains_key(*id)).map(emote).collect(),
+        removed: from.emotes.iter().filter(|(id, _)| !to.emotes.contains_key(*id)).map(emote).collect(),
+        renamed: to
+            .emotes
+            .iter()
+            .filter_map(|(id, name)| {
+                let previous = from.emotes.get(id)?;
+                (previous != name).then(|| RenamedEmote {
+                    id: id.clone(),
+                    from: previous.clone(),
+                    to: name.clone(),
+                })
+            })
+            .collect(),
+    }
+}
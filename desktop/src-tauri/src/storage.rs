@@ -0,0 +1,163 @@
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const LOCK_FILE: &str = ".mojify.lock";
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, ".bak")
+}
+
+// Writes `contents` to a temp file next to `path` and renames it into place,
+// so readers see either the old file or the new one, never a torn write
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp_path = sibling(path, &format!(".{}.tmp", std::process::id()));
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn is_json(content: &[u8]) -> bool {
+    serde_json::from_slice::<serde_json::Value>(content).is_ok()
+}
+
+// Like `write_atomic`, but first rolls the current file over to `.bak` as long
+// as it still holds valid JSON, so a bad file never replaces a good backup
+pub fn write_json_with_backup(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Ok(current) = fs::read(path) {
+        if is_json(&current) {
+            write_atomic(&backup_path(path), &current)?;
+        }
+    }
+    write_atomic(path, contents)
+}
+
+// A JSON file as read from disk
+pub enum JsonRead {
+    Missing,
+    Ok(String),
+    // The file was unreadable JSON and has been restored from its backup
+    Restored(String),
+    // Unreadable JSON with no usable backup
+    Corrupt,
+}
+
+pub fn read_json_with_recovery(path: &Path) -> io::Result<JsonRead> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(JsonRead::Missing),
+        Err(e) => return Err(e),
+    };
+    if is_json(&content) {
+        return Ok(JsonRead::Ok(String::from_utf8_lossy(&content).into_owned()));
+    }
+    match fs::read(backup_path(path)) {
+        Ok(backup) if is_json(&backup) => {
+            write_atomic(path, &backup)?;
+            Ok(JsonRead::Restored(String::from_utf8_lossy(&backup).into_owned()))
+        }
+        _ => Ok(JsonRead::Corrupt),
+    }
+}
+
+// Advisory lock on the MojifyData directory, held for as long as a sync reads
+// and rewrites the files in it. Released on drop.
+pub struct DataDirLock {
+    file: File,
+}
+
+impl DataDirLock {
+    // `None` when another process or sync already holds the lock
+    pub fn try_acquire(mojify_data_dir: &Path) -> io::Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(mojify_data_dir.join(LOCK_FILE))?;
+        match file.try_lock_exclusive() {
+            Ok(()) => Ok(Some(Self { file })),
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drop for DataDirLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mojify-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> JsonRead {
+        read_json_with_recovery(path).unwrap()
+    }
+
+    #[test]
+    fn writes_roll_the_previous_file_over_to_its_backup() {
+        let dir = scratch_dir("backup");
+        let path = dir.join("mapping.json");
+        assert!(matches!(read(&path), JsonRead::Missing));
+
+        write_json_with_backup(&path, b"{\"a\":1}").unwrap();
+        write_json_with_backup(&path, b"{\"a\":2}").unwrap();
+        assert!(matches!(read(&path), JsonRead::Ok(content) if content == "{\"a\":2}"));
+        assert_eq!(fs::read(backup_path(&path)).unwrap(), b"{\"a\":1}");
+
+        // A damaged file never replaces a good backup
+        fs::write(&path, b"{\"a\":").unwrap();
+        write_json_with_backup(&path, b"{\"a\":3}").unwrap();
+        assert_eq!(fs::read(backup_path(&path)).unwrap(), b"{\"a\":1}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_damaged_file_is_restored_from_a_good_backup() {
+        let dir = scratch_dir("restore");
+        let path = dir.join("mapping.json");
+        fs::write(&path, b"{\"a\":").unwrap();
+        fs::write(backup_path(&path), b"{\"a\":1}").unwrap();
+
+        assert!(matches!(read(&path), JsonRead::Restored(content) if content == "{\"a\":1}"));
+        assert_eq!(fs::read(&path).unwrap(), b"{\"a\":1}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_damaged_file_without_a_good_backup_is_reported_corrupt() {
+        let dir = scratch_dir("corrupt");
+        let path = dir.join("mapping.json");
+        fs::write(&path, b"{\"a\":").unwrap();
+        assert!(matches!(read(&path), JsonRead::Corrupt));
+
+        fs::write(backup_path(&path), b"not json").unwrap();
+        assert!(matches!(read(&path), JsonRead::Corrupt));
+        // Left as it was for someone to look at
+        assert_eq!(fs::read(&path).unwrap(), b"{\"a\":");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
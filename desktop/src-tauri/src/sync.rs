@@ -0,0 +1,962 @@
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{Window, Wry};
+
+use crate::events::{
+    emit_event, ChannelFinished, ChannelStarted, ChannelStatus, EmoteProgress, EmoteStatus, CHANNEL_FINISHED_EVENT,
+    CHANNEL_STARTED_EVENT, EMOTE_PROGRESS_EVENT,
+};
+use crate::download::{can_decode, fetch_file, save_emote_file, save_first_frame, HostLimiter};
+use crate::http::HttpClient;
+use crate::index::{EmoteIndex, IndexedEmote};
+use crate::jobs::{JobControl, JobId};
+use crate::mapping::{
+    namespaced_key, remove_paths, EmoteMapping, EmoteMeta, EmoteNames, KeyClaims, KeyCollision, KeyOwner,
+    MappingEntry,
+};
+use crate::prune::{prune_folder, PruneMode};
+use crate::snapshots::{record_snapshot, ChannelSnapshot};
+use crate::report::{ChannelReport, EmoteFailure, Failure, FailureReason};
+use crate::provider::{ChannelInput, DownloadCandidate, EmoteFile, EmoteProvider, ProviderEmote, ResolvedChannel};
+use crate::paths::{
+    emote_file_name, file_extension, mapping_path, safe_join, sanitize_filename, scaled_file_name, static_file_name,
+    suffixed_filename,
+};
+use crate::{emit_log, CommandError};
+
+// Everything a provider sync needs besides the provider itself
+pub struct SyncContext<'a> {
+    pub window: &'a Window<Wry>,
+    // Tags the typed progress events so the frontend can tell jobs apart
+    pub job_id: JobId,
+    pub client: &'a HttpClient,
+    pub mojify_data_dir: &'a Path,
+    pub limiter: &'a HostLimiter,
+    pub control: &'a JobControl,
+    pub prune_mode: PruneMode,
+    pub emote_names: EmoteNames,
+    pub channel_priority: &'a [String],
+    // Extra sizes stored next to each emote's full-size file
+    pub scales: &'a [u32],
+    // Read while emotes sync, updated as each channel is folded in
+    pub index: &'a Mutex<EmoteIndex>,
+    // Emotes of one channel in flight at once (the limiter still caps each host)
+    pub emote_concurrency: usize,
+    pub channel_concurrency: usize,
+}
+
+// Fields missing from older reports in the history read as their defaults
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelStats {
+    pub processed: usize,
+    pub downloaded: usize,
+    pub skipped: usize,
+    // Already on disk under an older name, moved instead of downloaded
+    pub renamed: usize,
+    pub failed: usize,
+    pub failed_emotes: Vec<EmoteFailure>,
+    // Bytes written by fresh downloads
+    pub bytes: u64,
+    // Files of dropped emotes that were deleted or archived
+    pub pruned: usize,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SyncTotals {
+    pub processed: usize,
+    pub downloaded: usize,
+    pub skipped: usize,
+    pub renamed: usize,
+    pub failed: usize,
+    // Already broken down per channel in the report
+    #[serde(skip)]
+    pub failed_emotes: Vec<String>,
+    pub bytes: u64,
+    pub pruned: usize,
+    pub collisions: Vec<KeyCollision>,
+    // Requests that had to be retried, API calls and downloads alike
+    pub retries: usize,
+}
+
+impl SyncTotals {
+    pub fn add(&mut self, label: &str, stats: &ChannelStats) {
+        self.processed += stats.processed;
+        self.downloaded += stats.downloaded;
+        self.skipped += stats.skipped;
+        self.renamed += stats.renamed;
+        self.failed += stats.failed;
+        self.bytes += stats.bytes;
+        self.pruned += stats.pruned;
+        self.failed_emotes
+            .extend(stats.failed_emotes.iter().map(|failed| format!("{}: {}", label, failed)));
+    }
+}
+
+pub fn log_channel_summary(window: &Window<Wry>, label: &str, stats: &ChannelStats) {
+    emit_log(window, format!("Backend: === CHANNEL {} SUMMARY ===", label));
+    emit_log(window, format!("Backend: Total emotes processed: {}", stats.processed));
+    emit_log(window, format!("Backend: Successfully downloaded: {} emotes", stats.downloaded));
+    emit_log(window, format!("Backend: Skipped (already existed): {} emotes", stats.skipped));
+    if stats.renamed > 0 {
+        emit_log(window, format!("Backend: Renamed (same emote, new name): {} emotes", stats.renamed));
+    }
+    emit_log(window, format!("Backend: Failed to download: {} emotes", stats.failed));
+    if stats.pruned > 0 {
+        emit_log(window, format!("Backend: Pruned (dropped by the channel): {} files", stats.pruned));
+    }
+
+    // If any failures, log them in a summarized way
+    if !stats.failed_emotes.is_empty() {
+        emit_log(window, "Backend: Failed emotes for this channel:".to_string());
+        for (index, failed_emote) in stats.failed_emotes.iter().enumerate() {
+            emit_log(window, format!("Backend:   {}. {}", index + 1, failed_emote));
+        }
+    }
+}
+
+pub fn log_overall_summary(window: &Window<Wry>, totals: &SyncTotals) {
+    emit_log(window, "Backend: ======= OVERALL SUMMARY =======".to_string());
+    emit_log(window, format!("Backend: Total emotes processed: {}", totals.processed));
+    emit_log(window, format!("Backend: Successfully downloaded: {} emotes", totals.downloaded));
+    emit_log(window, format!("Backend: Skipped (already existed): {} emotes", totals.skipped));
+    emit_log(window, format!("Backend: Renamed (same emote, new name): {} emotes", totals.renamed));
+    emit_log(window, format!("Backend: Failed to download: {} emotes", totals.failed));
+    emit_log(window, format!("Backend: Pruned (dropped by channels): {} files", totals.pruned));
+    emit_log(window, format!("Backend: Retried requests: {}", totals.retries));
+
+    if !totals.collisions.is_empty() {
+        emit_log(window, format!("Backend: Name collisions between channels: {} (limited to first 50):", totals.collisions.len()));
+        for collision in totals.collisions.iter().take(50) {
+            let message = if collision.namespaced || collision.kept.namespace == collision.dropped.namespace {
+                format!("Backend:   {} kept by {}, not by {}", collision.key, collision.kept, collision.dropped)
+            } else {
+                let namespaced = namespaced_key(&collision.dropped.namespace, &collision.key);
+                format!("Backend:   {} kept by {}, {} has it as {}", collision.key, collision.kept, collision.dropped, namespaced)
+            };
+            emit_log(window, message);
+        }
+    }
+
+    // If any failures, log them in a summarized way
+    if !totals.failed_emotes.is_empty() {
+        emit_log(window, "Backend: Failed emotes (limited to first 50):".to_string());
+        for (index, failed_emote) in totals.failed_emotes.iter().take(50).enumerate() {
+            emit_log(window, format!("Backend:   {}. {}", index + 1, failed_emote));
+        }
+
+        if totals.failed_emotes.len() > 50 {
+            emit_log(window, format!("Backend: ... and {} more failed emotes", totals.failed_emotes.len() - 50));
+        }
+    }
+}
+
+// File name stems by emote id. Emotes whose names only differ in case, or in
+// characters `sanitize_filename` replaces, would end up sharing one file on
+// case-insensitive filesystems, so each of them gets its id appended instead.
+// The suffix only depends on the listing, so names stay the same across
+// syncs for as long as the collision lasts.
+fn file_stems(emotes: &[ProviderEmote]) -> HashMap<String, String> {
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    let folded = |emote: &ProviderEmote| sanitize_filename(&emote.code).to_lowercase();
+    for emote in emotes {
+        *groups.entry(folded(emote)).or_default() += 1;
+    }
+    emotes
+        .iter()
+        .map(|emote| {
+            let stem = if groups[&folded(emote)] > 1 {
+                suffixed_filename(&emote.code, &emote.id)
+            } else {
+                sanitize_filename(&emote.code)
+            };
+            (emote.id.clone(), stem)
+        })
+        .collect()
+}
+
+// Where the listed emotes were stored last time, for moving files of renamed
+// emotes. Before file names were made unique, emotes like `Kappa` and `kappa`
+// could end up indexed with the same file; that file belongs to none of them
+// for sure, so those emotes are downloaded again instead of racing to move it.
+fn renamable(
+    index: &EmoteIndex,
+    channel_key: &str,
+    owners: &HashMap<String, Vec<String>>,
+    emotes: &[ProviderEmote],
+) -> HashMap<String, IndexedEmote> {
+    emotes
+        .iter()
+        .filter_map(|emote| {
+            let indexed = index.find(&emote.id, channel_key)?;
+            (owners.get(&indexed.path).map_or(0, Vec::len) == 1).then(|| (emote.id.clone(), indexed.clone()))
+        })
+        .collect()
+}
+
+// Moves the files of renamed emotes to their new names before anything is
+// downloaded. Every file is parked under a temporary name first, so emotes
+// that swapped names don't block each other. Returns the new path and the
+// old paths it replaces by emote id, and updates `owners` to match.
+fn move_renamed(
+    ctx: &SyncContext<'_>,
+    folder: &Path,
+    stems: &HashMap<String, String>,
+    previous: &HashMap<String, IndexedEmote>,
+    owners: &mut HashMap<String, Vec<String>>,
+) -> HashMap<String, (String, Vec<String>)> {
+    let window = ctx.window;
+    let channel_emote_dir = ctx.mojify_data_dir.join(folder);
+    let mut parked = Vec::new();
+    for (id, indexed) in previous {
+        let old_path = ctx.mojify_data_dir.join(&indexed.path);
+        let (Some(old_stem), Some(extension)) = (old_path.file_stem(), file_extension(&old_path)) else {
+            continue;
+        };
+        let old_stem = old_stem.to_string_lossy().into_owned();
+        let is_current = old_path.file_name().is_some_and(|name| *name == *emote_file_name(&stems[id], &extension));
+        if is_current || !old_path.exists() {
+            continue;
+        }
+        let temp_stem = suffixed_filename(".renaming", id);
+        let temp_path = channel_emote_dir.join(emote_file_name(&temp_stem, &extension));
+        if let Err(e) = fs::rename(&old_path, &temp_path) {
+            emit_log(window, format!("Backend: Failed to rename {}: {}", indexed.path, e));
+            continue;
+        }
+        let mut moved = vec![indexed.path.clone()];
+        moved.extend(move_companions(ctx, folder, &old_stem, &temp_stem, &extension));
+        parked.push((id, temp_stem, extension, moved));
+    }
+
+    let mut renamed = HashMap::new();
+    for (id, temp_stem, extension, moved) in parked {
+        let stem = &stems[id];
+        let filename = emote_file_name(stem, &extension);
+        let temp_path = channel_emote_dir.join(emote_file_name(&temp_stem, &extension));
+        let new_path = channel_emote_dir.join(&filename);
+        // Whatever still sits under the new name belongs to no listed emote.
+        // `rename` won't replace an existing file on Windows.
+        if new_path.exists() {
+            let _ = fs::remove_file(&new_path);
+        }
+        // Left under its temporary name, the file is pruned later and the
+        // emote downloaded again
+        if let Err(e) = fs::rename(&temp_path, &new_path) {
+            emit_log(window, format!("Backend: Failed to rename {}: {}", moved[0], e));
+            continue;
+        }
+        move_companions(ctx, folder, &temp_stem, stem, &extension);
+        emit_log(window, format!("Backend: Renamed {} to {} (ID: {})", moved[0], filename, id));
+        renamed.insert(id.clone(), (mapping_path(&folder.join(&filename)), moved, extension));
+    }
+
+    // Old paths another renamed emote moved into aren't gone
+    let taken: HashSet<String> = renamed
+        .iter()
+        .flat_map(|(id, (_, _, extension))| {
+            let stem = &stems[id];
+            std::iter::once(emote_file_name(stem, extension)).chain(companion_files(ctx.scales, stem, extension))
+        })
+        .map(|filename| mapping_path(&folder.join(filename)))
+        .collect();
+    renamed
+        .into_iter()
+        .map(|(id, (path, mut moved, _))| {
+            for old_path in &moved {
+                if let Some(ids) = owners.get_mut(old_path) {
+                    ids.retain(|owner| *owner != id);
+                }
+            }
+            owners.insert(path.clone(), vec![id.clone()]);
+            moved.retain(|old_path| !taken.contains(old_path));
+            (id, (path, moved))
+        })
+        .collect()
+}
+
+fn emote_filename(stem: &str, candidate: &DownloadCandidate) -> String {
+    emote_file_name(stem, &candidate.extension)
+}
+
+// What a channel sync contributes to the mapping, applied once the channel
+// is done so parallel channels never race on the shared map
+#[derive(Debug, Default)]
+pub struct ChannelSync {
+    pub stats: ChannelStats,
+    pub entries: Vec<MappingEntry>,
+    // Some emotes were never started because the job was cancelled
+    pub cancelled: bool,
+    // Relative paths of files removed from the channel folder
+    pub pruned: Vec<String>,
+    // Relative paths of files that were moved to an emote's new name
+    pub renamed_from: Vec<String>,
+    // Relative paths of files removed for the same emote in a more preferred
+    // format
+    pub superseded: Vec<String>,
+    // Index updates for every emote that ended up on disk
+    pub indexed: Vec<(String, IndexedEmote)>,
+}
+
+// Outcomes carry the stored file's path relative to MojifyData
+// Downloads and skips list the removed files of the emote's other formats
+enum EmoteOutcome {
+    Downloaded(String, u64, Vec<String>),
+    Skipped(String, Vec<String>),
+    // The files of a renamed emote were moved from the listed paths
+    Renamed(String, Vec<String>),
+    Failed(EmoteFailure),
+    // Never started because the job was cancelled
+    Cancelled,
+}
+
+impl EmoteOutcome {
+    fn path(&self) -> Option<&str> {
+        match self {
+            EmoteOutcome::Downloaded(path, _, _) | EmoteOutcome::Skipped(path, _) | EmoteOutcome::Renamed(path, _) => {
+                Some(path)
+            }
+            EmoteOutcome::Failed(_) | EmoteOutcome::Cancelled => None,
+        }
+    }
+}
+
+// Stores the first frame of an animated emote next to its file at `path`:
+// the provider's static variant when it has one, otherwise frame 0 of our
+// own copy as a PNG. Formats we can't decode, like AVIF, have their frame
+// taken from another advertised format instead. Returns the static file's
+// path and the bytes written.
+async fn sync_static(
+    ctx: &SyncContext<'_>,
+    folder: &Path,
+    stem: &str,
+    files: &[EmoteFile],
+    path: &str,
+) -> (Option<String>, u64) {
+    let relative = |filename: &str| mapping_path(&folder.join(filename));
+    let channel_emote_dir = ctx.mojify_data_dir.join(folder);
+    let format = file_extension(path).unwrap_or_default();
+    let largest = |matches: &dyn Fn(&EmoteFile) -> bool| {
+        files.iter().filter(|file| matches(file)).max_by_key(|file| file.scale)
+    };
+    let static_file = largest(&|file| file.format.eq_ignore_ascii_case(&format))
+        .and_then(|file| file.static_file.clone())
+        .or_else(|| largest(&|file| file.static_file.is_some()).and_then(|file| file.static_file.clone()));
+
+    if let Some(static_file) = static_file {
+        let filename = static_file_name(stem, &static_file.format);
+        let output_path = channel_emote_dir.join(&filename);
+        if output_path.exists() {
+            return (Some(relative(&filename)), 0);
+        }
+        match save_emote_file(ctx.client, ctx.limiter, &static_file.url, &output_path).await {
+            Ok(byte_count) => return (Some(relative(&filename)), byte_count as u64),
+            Err(failure) => emit_log(ctx.window, format!("Backend: Failed to download static {}: {}", stem, failure)),
+        }
+    }
+
+    let filename = static_file_name(stem, "png");
+    let output_path = channel_emote_dir.join(&filename);
+    if output_path.exists() {
+        return (Some(relative(&filename)), 0);
+    }
+    let (input, fetched) = if can_decode(&format) {
+        let input = fs::read(ctx.mojify_data_dir.join(path))
+            .map_err(|e| Failure::new(FailureReason::Io, format!("Failed to read {}: {}", path, e)));
+        (input, 0)
+    } else {
+        match largest(&|file| can_decode(&file.format)) {
+            Some(file) => {
+                let input = fetch_file(ctx.client, ctx.limiter, &file.url).await.map(|bytes| bytes.to_vec());
+                let fetched = input.as_ref().map_or(0, |bytes| bytes.len() as u64);
+                (input, fetched)
+            }
+            None => (Err(Failure::new(FailureReason::Decode, format!("Can't decode {} files", format))), 0),
+        }
+    };
+    let extracted = match input {
+        Ok(input) => {
+            let output_path = output_path.clone();
+            tokio::task::spawn_blocking(move || save_first_frame(&input, &output_path))
+                .await
+                .unwrap_or_else(|e| Err(Failure::new(FailureReason::Other, e.to_string())))
+        }
+        Err(failure) => Err(failure),
+    };
+    match extracted {
+        Ok(byte_count) => (Some(relative(&filename)), fetched + byte_count as u64),
+        Err(failure) => {
+            emit_log(ctx.window, format!("Backend: Failed to extract the first frame of {}: {}", stem, failure));
+            (None, fetched)
+        }
+    }
+}
+
+// Fetches the configured extra scales of an emote in the format of its
+// full-size file at `path`, returning every stored size by scale and the
+// bytes downloaded. A missing size is logged and left out rather than
+// failing the emote.
+async fn sync_scales(
+    ctx: &SyncContext<'_>,
+    folder: &Path,
+    stem: &str,
+    files: &[EmoteFile],
+    path: &str,
+) -> (BTreeMap<u32, String>, u64) {
+    let mut scales = BTreeMap::new();
+    let mut bytes = 0;
+    let Some(format) = file_extension(path) else {
+        return (scales, bytes);
+    };
+    let in_format = |file: &&EmoteFile| file.format.eq_ignore_ascii_case(&format);
+    if let Some(full) = files.iter().filter(in_format).map(|file| file.scale).max() {
+        scales.insert(full, path.to_string());
+    }
+
+    for &scale in ctx.scales {
+        if scales.contains_key(&scale) {
+            continue;
+        }
+        let Some(file) = files.iter().filter(in_format).find(|file| file.scale == scale) else {
+            continue;
+        };
+        let filename = scaled_file_name(stem, scale, &format);
+        let relative = mapping_path(&folder.join(&filename));
+        let output_path = ctx.mojify_data_dir.join(folder).join(&filename);
+        if !output_path.exists() {
+            match save_emote_file(ctx.client, ctx.limiter, &file.url, &output_path).await {
+                Ok(byte_count) => bytes += byte_count as u64,
+                Err(failure) => {
+                    emit_log(ctx.window, format!("Backend: Failed to download {} at {}x: {}", stem, scale, failure));
+                    continue;
+                }
+            }
+        }
+        scales.insert(scale, relative);
+    }
+    (scales, bytes)
+}
+
+// The smaller sizes and static frames that may sit next to an emote's file
+fn companion_files(scales: &[u32], stem: &str, extension: &str) -> Vec<String> {
+    let mut companions: Vec<String> = scales.iter().map(|&scale| scaled_file_name(stem, scale, extension)).collect();
+    companions.push(static_file_name(stem, extension));
+    if extension != "png" {
+        companions.push(static_file_name(stem, "png"));
+    }
+    companions
+}
+
+// Moves the companion files of a renamed emote along with its main file,
+// returning the old paths of the ones that were moved
+fn move_companions(ctx: &SyncContext<'_>, folder: &Path, old_stem: &str, stem: &str, extension: &str) -> Vec<String> {
+    let channel_emote_dir = ctx.mojify_data_dir.join(folder);
+    let old_files = companion_files(ctx.scales, old_stem, extension);
+    let new_files = companion_files(ctx.scales, stem, extension);
+    let mut moved = Vec::new();
+    for (old_file, new_file) in old_files.iter().zip(&new_files) {
+        let (old_path, new_path) = (channel_emote_dir.join(old_file), channel_emote_dir.join(new_file));
+        if !old_path.exists() {
+            continue;
+        }
+        // Left over from an emote that no longer goes by this name
+        if new_path.exists() {
+            let _ = fs::remove_file(&new_path);
+        }
+        match fs::rename(&old_path, &new_path) {
+            Ok(()) => moved.push(mapping_path(&folder.join(old_file))),
+            Err(e) => emit_log(ctx.window, format!("Backend: Failed to rename {}: {}", old_path.display(), e)),
+        }
+    }
+    moved
+}
+
+async fn sync_emote(
+    ctx: &SyncContext<'_>,
+    provider: &dyn EmoteProvider,
+    folder: &Path,
+    stem: &str,
+    owners: &HashMap<String, Vec<String>>,
+    renamed: Option<&(String, Vec<String>)>,
+    emote: ProviderEmote,
+) -> EmoteOutcome {
+    let outcome = store_emote(ctx, provider, folder, stem, owners, emote).await;
+    // Same emote id, different name: the channel renamed or re-aliased it,
+    // and `move_renamed` already moved the file we had. That file is what's
+    // stored unless a more preferred format was downloaded instead.
+    let Some((path, moved)) = renamed else {
+        return outcome;
+    };
+    match outcome {
+        EmoteOutcome::Downloaded(path, bytes, superseded) => {
+            EmoteOutcome::Downloaded(path, bytes, moved.iter().cloned().chain(superseded).collect())
+        }
+        EmoteOutcome::Skipped(path, superseded) => {
+            EmoteOutcome::Renamed(path, moved.iter().cloned().chain(superseded).collect())
+        }
+        _ => EmoteOutcome::Renamed(path.clone(), moved.clone()),
+    }
+}
+
+// Stores the emote under the first of the provider's candidates that is
+// already on disk or downloads, so a file in a less preferred format is only
+// kept while the preferred one can't be fetched. Files of the emote in the
+// other formats are removed.
+async fn store_emote(
+    ctx: &SyncContext<'_>,
+    provider: &dyn EmoteProvider,
+    folder: &Path,
+    stem: &str,
+    owners: &HashMap<String, Vec<String>>,
+    emote: ProviderEmote,
+) -> EmoteOutcome {
+    if !ctx.control.checkpoint().await {
+        return EmoteOutcome::Cancelled;
+    }
+    let window = ctx.window;
+    let channel_emote_dir = ctx.mojify_data_dir.join(folder);
+    emit_log(window, format!("Backend: Processing emote: {} (ID: {})", emote.code, emote.id));
+
+    let relative_path = |filename: &str| mapping_path(&folder.join(filename));
+
+    let failed = |failure: Failure| {
+        EmoteOutcome::Failed(EmoteFailure {
+            emote_id: emote.id.clone(),
+            emote_code: emote.code.clone(),
+            failure,
+        })
+    };
+
+    let candidates = provider.download_candidates(&emote);
+    if candidates.is_empty() {
+        let error_msg = "No downloadable file advertised";
+        emit_log(window, format!("Backend: Failed to download {}: {}", emote.code, error_msg));
+        return failed(Failure::new(FailureReason::NoFile, error_msg));
+    }
+
+    // A file already under one of our names is only this emote's when the
+    // index has it as this emote's, or as nobody's. Otherwise it belongs to
+    // an emote that was dropped or took another name, and is downloaded over
+    // along with its smaller sizes and static frame.
+    let filenames: Vec<String> = candidates.iter().map(|candidate| emote_filename(stem, candidate)).collect();
+    let mut owned = Vec::with_capacity(filenames.len());
+    for (candidate, filename) in candidates.iter().zip(&filenames) {
+        let exists = channel_emote_dir.join(filename).exists();
+        let is_ours = owners.get(&relative_path(filename)).map_or(true, |ids| ids.iter().all(|id| *id == emote.id));
+        if exists && !is_ours {
+            for companion in companion_files(ctx.scales, stem, &candidate.extension) {
+                let _ = fs::remove_file(channel_emote_dir.join(companion));
+            }
+        }
+        owned.push(exists && is_ours);
+    }
+
+    // Removes this emote's files in the formats other than the stored one,
+    // returning their paths
+    let supersede = |stored: usize| -> Vec<String> {
+        let kept = companion_files(ctx.scales, stem, &candidates[stored].extension);
+        let mut removed = Vec::new();
+        for (index, candidate) in candidates.iter().enumerate().filter(|(index, _)| *index != stored && owned[*index]) {
+            let mut files = companion_files(ctx.scales, stem, &candidate.extension);
+            files.retain(|file| !kept.contains(file));
+            files.insert(0, filenames[index].clone());
+            for filename in files {
+                let path = channel_emote_dir.join(&filename);
+                if !path.exists() {
+                    continue;
+                }
+                match fs::remove_file(&path) {
+                    Ok(()) => removed.push(relative_path(&filename)),
+                    Err(e) => emit_log(window, format!("Backend: Failed to remove {}: {}", path.display(), e)),
+                }
+            }
+        }
+        if !removed.is_empty() {
+            emit_log(window, format!("Backend: Replaced {} with {}", removed.join(", "), filenames[stored]));
+        }
+        removed
+    };
+
+    let mut last_error = Failure::new(FailureReason::NoFile, "No downloadable file advertised");
+    for (index, candidate) in candidates.iter().enumerate() {
+        let filename = &filenames[index];
+        let output_path = channel_emote_dir.join(filename);
+        if owned[index] {
+            emit_log(window, format!("Backend: Skipping existing emote: {} at {}", emote.code, output_path.display()));
+            return EmoteOutcome::Skipped(relative_path(filename), supersede(index));
+        }
+        emit_log(window, format!("Backend: Attempting to download {}: {} to {}", emote.code, candidate.url, output_path.display()));
+        match save_emote_file(ctx.client, ctx.limiter, &candidate.url, &output_path).await {
+            Ok(byte_count) => {
+                emit_log(window, format!("Backend: Saved {} ({} bytes) to {}", emote.code, byte_count, output_path.display()));
+                return EmoteOutcome::Downloaded(relative_path(filename), byte_count as u64, supersede(index));
+            }
+            Err(failure) => {
+                emit_log(window, format!("Backend: Failed to download {}: {}", emote.code, failure));
+                last_error = failure;
+            }
+        }
+    }
+    failed(last_error)
+}
+
+// Lists a resolved channel's emotes and downloads them into
+// `<mojify_data_dir>/<provider folder>/<channel dir>`, a bounded number at a
+// time. Every file that ends up on disk is returned as a mapping entry.
+pub async fn sync_channel(
+    ctx: &SyncContext<'_>,
+    provider: &dyn EmoteProvider,
+    channel: &ResolvedChannel,
+) -> Result<ChannelSync, CommandError> {
+    let mut result = ChannelSync::default();
+    let window = ctx.window;
+    // Relative to MojifyData; checked before anything touches the disk
+    let folder = safe_join(Path::new(""), &[provider.folder(), &channel.dir_name])?;
+
+    let emotes = provider.list_emotes(ctx.client, channel).await?;
+    let total = emotes.len();
+    emit_event(window, CHANNEL_STARTED_EVENT, ChannelStarted {
+        job_id: ctx.job_id,
+        provider: provider.name().to_string(),
+        channel: channel.label.clone(),
+        total,
+    });
+
+    // The listing is what changed on the channel's side, so it is recorded
+    // even if some of the downloads below fail
+    let taken_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let snapshot = ChannelSnapshot::new(taken_at, provider.name(), channel, &emotes);
+    if let Err(e) = record_snapshot(ctx.mojify_data_dir, provider.folder(), &channel.dir_name, snapshot) {
+        emit_log(window, format!("Backend: Failed to record snapshot for {}: {}", channel.label, e));
+    }
+
+    if emotes.is_empty() {
+        emit_log(window, format!("Backend: No {} emotes for channel {}", provider.name(), channel.label));
+        return Ok(result);
+    }
+    emit_log(window, format!("Backend: Found {} {} emotes for {}", emotes.len(), provider.name(), channel.label));
+
+    fs::create_dir_all(ctx.mojify_data_dir.join(&folder))?;
+
+    let stems = file_stems(&emotes);
+    let stems = &stems;
+    let channel_key = mapping_path(&folder);
+    let (mut owners, previous) = {
+        let index = ctx.index.lock().unwrap_or_else(|e| e.into_inner());
+        let owners = index.owners(&channel_key);
+        let previous = renamable(&index, &channel_key, &owners, &emotes);
+        (owners, previous)
+    };
+    let renamed = move_renamed(ctx, &folder, stems, &previous, &mut owners);
+    let (owners, renamed) = (&owners, &renamed);
+    let suffixed = emotes.iter().filter(|emote| stems[&emote.id] != sanitize_filename(&emote.code)).count();
+    if suffixed > 0 {
+        emit_log(window, format!("Backend: {} emotes in {} share a file name, storing them under their IDs", suffixed, channel.label));
+    }
+
+    // The name each listed emote is preferably stored under, plus whatever
+    // ends up stored below; anything else in the folder belongs to an emote
+    // the channel dropped, or is the emote in a format no longer preferred
+    let mut keep: HashSet<String> = emotes
+        .iter()
+        .flat_map(|emote| {
+            let stem = &stems[&emote.id];
+            provider
+                .download_candidates(emote)
+                .first()
+                .map(|candidate| {
+                    let companions = companion_files(ctx.scales, stem, &candidate.extension);
+                    std::iter::once(emote_filename(stem, candidate)).chain(companions).collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        })
+        .collect();
+
+    // `buffered` keeps the outcomes in listing order, so the mapping and the
+    // failure list come out the same no matter which download finishes first
+    let folder = &folder;
+    let mut outcomes = stream::iter(emotes)
+        .map(|emote| async move {
+            let bare_keys = ctx.emote_names.keys(&emote);
+            let namespaced_keys: Vec<String> =
+                bare_keys.iter().map(|key| namespaced_key(&channel.namespace, key)).collect();
+            let keys: Vec<(String, bool)> = bare_keys
+                .into_iter()
+                .map(|key| (key, false))
+                .chain(namespaced_keys.into_iter().map(|key| (key, true)))
+                .collect();
+            let meta = EmoteMeta::new(provider.name(), &channel.namespace, &emote);
+            let stem = &stems[&emote.id];
+            let files = emote.files.clone();
+            let animated = emote.animated;
+            let moved = renamed.get(&emote.id);
+            let outcome = sync_emote(ctx, provider, folder, stem, owners, moved, emote).await;
+            let scales = match outcome.path() {
+                Some(path) if !ctx.scales.is_empty() => sync_scales(ctx, folder, stem, &files, path).await,
+                _ => (BTreeMap::new(), 0),
+            };
+            let still = match outcome.path() {
+                Some(path) if animated => sync_static(ctx, folder, stem, &files, path).await,
+                _ => (None, 0),
+            };
+            (meta, keys, outcome, scales, still)
+        })
+        .buffered(ctx.emote_concurrency.max(1));
+    while let Some((meta, keys, outcome, (scales, scale_bytes), (static_path, static_bytes))) = outcomes.next().await {
+        result.stats.bytes += scale_bytes + static_bytes;
+        // Fresh downloads and renames take their keys over, files that were
+        // already on disk only fill in unused keys
+        let (status, bytes, error, stored) = match outcome {
+            EmoteOutcome::Downloaded(path, bytes, superseded) => {
+                result.stats.downloaded += 1;
+                result.stats.bytes += bytes;
+                result.superseded.extend(superseded);
+                (EmoteStatus::Downloaded, bytes, None, Some((path, true)))
+            }
+            EmoteOutcome::Skipped(path, superseded) => {
+                result.stats.skipped += 1;
+                result.superseded.extend(superseded);
+                (EmoteStatus::Skipped, 0, None, Some((path, false)))
+            }
+            EmoteOutcome::Renamed(path, old_paths) => {
+                result.stats.renamed += 1;
+                result.renamed_from.extend(old_paths);
+                (EmoteStatus::Renamed, 0, None, Some((path, true)))
+            }
+            EmoteOutcome::Failed(failure) => {
+                result.stats.failed += 1;
+                let error = failure.failure.clone();
+                result.stats.failed_emotes.push(failure);
+                (EmoteStatus::Failed, 0, Some(error), None)
+            }
+            EmoteOutcome::Cancelled => {
+                result.cancelled = true;
+                continue;
+            }
+        };
+        if let Some((path, replace)) = stored {
+            let meta = EmoteMeta {
+                format: file_extension(&path),
+                ..meta.clone()
+            };
+            result.entries.extend(keys.iter().map(|(key, namespaced)| MappingEntry {
+                key: key.clone(),
+                path: path.clone(),
+                replace,
+                namespaced: *namespaced,
+                meta: Some(meta.clone()),
+                scales: scales.clone(),
+                static_path: static_path.clone(),
+            }));
+            result.indexed.push((meta.id.clone(), IndexedEmote {
+                channel: channel_key.clone(),
+                path,
+                names: keys.into_iter().map(|(key, _)| key).collect(),
+                alias: meta.name.clone(),
+                original_name: meta.original_name.clone().filter(|original| *original != meta.name),
+            }));
+        }
+        result.stats.processed += 1;
+        emit_event(window, EMOTE_PROGRESS_EVENT, EmoteProgress {
+            job_id: ctx.job_id,
+            provider: provider.name().to_string(),
+            channel: channel.label.clone(),
+            emote_id: meta.id,
+            emote_code: meta.name,
+            status,
+            bytes,
+            error,
+            completed: result.stats.processed,
+            total,
+        });
+    }
+
+    // Only prune against a complete listing, and never after a cancel
+    if !result.cancelled && ctx.prune_mode != PruneMode::Off {
+        // Renamed files keep whatever extension they had, and static frames
+        // come in whatever format the provider serves them in
+        keep.extend(
+            result
+                .entries
+                .iter()
+                .flat_map(|entry| {
+                    std::iter::once(&entry.path).chain(entry.scales.values()).chain(entry.static_path.as_ref())
+                })
+                .filter_map(|path| Path::new(path).file_name().map(|name| name.to_string_lossy().into_owned())),
+        );
+        result.pruned = prune_folder(ctx.mojify_data_dir, folder, &keep, ctx.prune_mode)?;
+        result.stats.pruned = result.pruned.len();
+        for path in &result.pruned {
+            emit_log(window, format!("Backend: Pruned {} ({:?})", path, ctx.prune_mode));
+        }
+    }
+
+    Ok(result)
+}
+
+// A channel to sync: either raw user input still to be resolved by the
+// provider, or an already resolved channel such as a global set
+pub enum SyncTarget {
+    Input(Arc<dyn EmoteProvider>, ChannelInput),
+    Resolved(Arc<dyn EmoteProvider>, ResolvedChannel),
+}
+
+// A channel's report plus the mapping changes it brings, applied by
+// `sync_targets` in target order
+struct FinishedChannel {
+    report: ChannelReport,
+    namespace: String,
+    entries: Vec<MappingEntry>,
+    // Paths no longer on disk, pruned or moved by a rename
+    stale: Vec<String>,
+    indexed: Vec<(String, IndexedEmote)>,
+}
+
+fn finish_channel(
+    ctx: &SyncContext<'_>,
+    provider: &dyn EmoteProvider,
+    channel: String,
+    namespace: String,
+    result: Result<ChannelSync, CommandError>,
+) -> FinishedChannel {
+    let (report, entries, stale, indexed) = match result {
+        Ok(synced) => {
+            let status = if synced.cancelled { ChannelStatus::Cancelled } else { ChannelStatus::Completed };
+            let report = ChannelReport {
+                provider: provider.name().to_string(),
+                channel,
+                status,
+                stats: synced.stats,
+                error: None,
+            };
+            let stale = synced.pruned.into_iter().chain(synced.renamed_from).chain(synced.superseded).collect();
+            (report, synced.entries, stale, synced.indexed)
+        }
+        Err(e) => {
+            let report = ChannelReport {
+                provider: provider.name().to_string(),
+                channel,
+                status: ChannelStatus::Failed,
+                stats: ChannelStats::default(),
+                error: Some(Failure::from(&e)),
+            };
+            (report, Vec::new(), Vec::new(), Vec::new())
+        }
+    };
+    emit_event(ctx.window, CHANNEL_FINISHED_EVENT, ChannelFinished {
+        job_id: ctx.job_id,
+        provider: report.provider.clone(),
+        channel: report.channel.clone(),
+        status: report.status,
+        processed: report.stats.processed,
+        downloaded: report.stats.downloaded,
+        skipped: report.stats.skipped,
+        renamed: report.stats.renamed,
+        failed: report.stats.failed,
+        bytes: report.stats.bytes,
+        pruned: report.stats.pruned,
+        error: report.error.clone(),
+    });
+    FinishedChannel {
+        report,
+        namespace,
+        entries,
+        stale,
+        indexed,
+    }
+}
+
+async fn sync_target(
+    ctx: &SyncContext<'_>,
+    target: SyncTarget,
+) -> Option<FinishedChannel> {
+    if !ctx.control.checkpoint().await {
+        return None;
+    }
+    let (provider, channel) = match target {
+        SyncTarget::Resolved(provider, channel) => (provider, channel),
+        SyncTarget::Input(provider, input) => {
+            emit_log(ctx.window, format!("Backend: Processing {} channel ID: {}", provider.name(), input));
+            match provider.resolve_channel(ctx.client, &input).await {
+                Ok(Some(channel)) => (provider, channel),
+                Ok(None) => {
+                    emit_log(ctx.window, format!("Backend: Channel {} has no {} emotes", input, provider.name()));
+                    return None;
+                }
+                Err(e) => {
+                    emit_log(ctx.window, format!("Backend: Failed to resolve {} channel {}: {}", provider.name(), input, e));
+                    return Some(finish_channel(ctx, provider.as_ref(), input.to_string(), input.namespace(), Err(e)));
+                }
+            }
+        }
+    };
+    let result = sync_channel(ctx, provider.as_ref(), &channel).await;
+    Some(finish_channel(ctx, provider.as_ref(), channel.label, channel.namespace, result))
+}
+
+// Syncs `targets` with up to `ctx.channel_concurrency` channels in flight,
+// then folds each finished channel into the mapping and `totals` in target
+// order. Provider errors are logged and reported, not propagated, so one
+// broken channel doesn't abort the whole run.
+pub async fn sync_targets(
+    ctx: &SyncContext<'_>,
+    targets: Vec<SyncTarget>,
+    global_emote_mapping: &mut EmoteMapping,
+    totals: &mut SyncTotals,
+) -> Vec<ChannelReport> {
+    let mut reports = Vec::new();
+    let mut claims = KeyClaims::default();
+    let rank = |namespace: &str| {
+        ctx.channel_priority
+            .iter()
+            .position(|preferred| preferred.trim().eq_ignore_ascii_case(namespace))
+            .unwrap_or(ctx.channel_priority.len())
+    };
+    let mut channels = stream::iter(targets)
+        .map(|target| sync_target(ctx, target))
+        .buffered(ctx.channel_concurrency.max(1));
+    while let Some(finished) = channels.next().await {
+        let Some(FinishedChannel { report, namespace, entries, stale, indexed }) = finished else {
+            continue;
+        };
+        let label = format!("{} {}", report.provider, report.channel);
+        match &report.error {
+            None => {
+                let mut index = ctx.index.lock().unwrap_or_else(|e| e.into_inner());
+                remove_paths(global_emote_mapping, &stale);
+                index.remove_paths(&stale);
+                // Keys an emote no longer goes by, e.g. after switching
+                // `emote_names`, are dropped if they still point at its file
+                for (id, emote) in &indexed {
+                    let Some(previous) = index.find(id, &emote.channel) else {
+                        continue;
+                    };
+                    for name in previous.names.iter().filter(|name| !emote.names.contains(name)) {
+                        if global_emote_mapping.get(name).is_some_and(|record| record.path == previous.path) {
+                            global_emote_mapping.remove(name);
+                        }
+                    }
+                }
+                let complete = report.status == ChannelStatus::Completed;
+                claims.add(KeyOwner::new(&report.provider, &namespace), rank(&namespace), entries, complete);
+                for (id, emote) in indexed {
+                    index.insert(&id, emote);
+                }
+                log_channel_summary(ctx.window, &label, &report.stats);
+                totals.add(&label, &report.stats);
+            }
+            Some(e) => emit_log(ctx.window, format!("Backend: Sync failed for {}: {}", label, e)),
+        }
+        reports.push(report);
+    }
+    claims.resolve(global_emote_mapping, rank);
+    totals.collisions = claims.collisions;
+    totals.retries = ctx.client.retries();
+    reports
+}
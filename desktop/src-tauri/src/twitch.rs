@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tauri::{Window, Wry};
+
+use crate::http::HttpClient;
+use crate::{emit_log, CommandError};
+
+const TWITCH_ID_CACHE_FILE: &str = "twitch_ids.json";
+const KICK_ID_CACHE_FILE: &str = "kick_ids.json";
+
+const SEVENTV_USER_SEARCH_QUERY: &str =
+    "query SearchUsers($query: String!) { users(query: $query) { id username connections { id platform username } } }";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvSearchConnection {
+    id: String,
+    platform: String,
+    username: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvSearchUser {
+    id: String,
+    username: String,
+    #[serde(default)]
+    connections: Vec<SevenTvSearchConnection>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvSearchData {
+    #[serde(default)]
+    users: Vec<SevenTvSearchUser>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SevenTvSearchResponse {
+    data: Option<SevenTvSearchData>,
+}
+
+// Twitch logins are lowercase alphanumerics and underscores; ids are numeric.
+// The same goes for Kick slugs and user ids.
+pub fn is_twitch_id(input: &str) -> bool {
+    !input.is_empty() && input.chars().all(|c| c.is_ascii_digit())
+}
+
+// Name -> numeric user id, persisted as JSON in MojifyData
+struct IdCache {
+    path: PathBuf,
+    ids: HashMap<String, String>,
+}
+
+impl IdCache {
+    fn load(path: PathBuf) -> Self {
+        let ids = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, ids }
+    }
+
+    // A failed write only costs another lookup next time, so it is logged
+    // rather than failing a lookup that worked
+    fn insert(&mut self, window: &Window<Wry>, name: String, id: String) {
+        self.ids.insert(name, id);
+        let written = serde_json::to_string_pretty(&self.ids)
+            .map_err(CommandError::from)
+            .and_then(|content| fs::write(&self.path, content).map_err(CommandError::from));
+        if let Err(e) = written {
+            emit_log(window, format!("Backend: Failed to cache channel IDs in {}: {}", self.path.display(), e));
+        }
+    }
+}
+
+// Turns Twitch logins and Kick slugs into numeric user ids, which is what the
+// 7TV, BTTV and FFZ endpoints expect. Twitch lookups go through a
+// configurable endpoint when one is set (`{login}` is replaced in the URL,
+// and the response must carry an `id` field, optionally inside an array),
+// otherwise through the 7TV user search, which Kick lookups always use.
+// Results are cached per name in MojifyData.
+pub struct ChannelIdResolver {
+    seventv_gql_url: String,
+    lookup_url: Option<String>,
+    twitch: IdCache,
+    kick: IdCache,
+}
+
+impl ChannelIdResolver {
+    pub fn new(seventv_api_base: &str, lookup_url: Option<String>, mojify_data_dir: PathBuf) -> Self {
+        Self {
+            seventv_gql_url: format!("{}/gql", seventv_api_base.trim_end_matches('/')),
+            lookup_url: lookup_url.filter(|url| !url.trim().is_empty()),
+            twitch: IdCache::load(mojify_data_dir.join(TWITCH_ID_CACHE_FILE)),
+            kick: IdCache::load(mojify_data_dir.join(KICK_ID_CACHE_FILE)),
+        }
+    }
+
+    pub async fn resolve(
+        &mut self,
+        window: &Window<Wry>,
+        client: &HttpClient,
+        login: &str,
+    ) -> Result<String, CommandError> {
+        let login = login.to_lowercase();
+        if let Some(id) = self.twitch.ids.get(&login) {
+            return Ok(id.clone());
+        }
+
+        let id = match &self.lookup_url {
+            Some(lookup_url) => lookup_with_endpoint(client, lookup_url, &login).await?,
+            None => lookup_with_seventv(client, &self.seventv_gql_url, "twitch", &login).await?,
+        }
+        .ok_or_else(|| CommandError::UnknownTwitchLogin(login.clone()))?;
+
+        self.twitch.insert(window, login, id.clone());
+        Ok(id)
+    }
+
+    // 7TV only knows Kick accounts by their numeric user id, so a channel
+    // slug is looked up through the Kick connection of a 7TV user
+    pub async fn resolve_kick(
+        &mut self,
+        window: &Window<Wry>,
+        client: &HttpClient,
+        slug: &str,
+    ) -> Result<String, CommandError> {
+        let slug = slug.to_lowercase();
+        if let Some(id) = self.kick.ids.get(&slug) {
+            return Ok(id.clone());
+        }
+
+        let id = lookup_with_seventv(client, &self.seventv_gql_url, "kick", &slug)
+            .await?
+            .ok_or_else(|| CommandError::UnknownKickChannel(slug.clone()))?;
+
+        self.kick.insert(window, slug, id.clone());
+        Ok(id)
+    }
+}
+
+async fn lookup_with_endpoint(
+    client: &HttpClient,
+    lookup_url: &str,
+    login: &str,
+) -> Result<Option<String>, CommandError> {
+    let url = lookup_url.replace("{login}", login);
+    let response = client.get(&url).await?;
+    if !response.status.is_success() {
+        return Err(CommandError::HttpStatus {
+            status: response.status.as_u16(),
+            url,
+        });
+    }
+    let body: Value = serde_json::from_slice(&response.body)?;
+    let user = match &body {
+        Value::Array(users) => users.first(),
+        other => Some(other),
+    };
+    Ok(user.and_then(|user| match user.get("id") {
+        Some(Value::String(id)) => Some(id.clone()),
+        Some(Value::Number(id)) => Some(id.to_string()),
+        _ => None,
+    }))
+}
+
+// Finds the `platform` connection named `login` among the 7TV users the
+// search turns up
+async fn lookup_with_seventv(
+    client: &HttpClient,
+    gql_url: &str,
+    platform: &str,
+    login: &str,
+) -> Result<Option<String>, CommandError> {
+    let body = json!({
+        "query": SEVENTV_USER_SEARCH_QUERY,
+        "variables": { "query": login },
+    });
+    let response = client.fetch(|client| client.post(gql_url).json(&body)).await?;
+    if !response.status.is_success() {
+        return Err(CommandError::HttpStatus {
+            status: response.status.as_u16(),
+            url: gql_url.to_string(),
+        });
+    }
+    let search: SevenTvSearchResponse = serde_json::from_slice(&response.body)?;
+    Ok(search
+        .data
+        .into_iter()
+        .flat_map(|data| data.users)
+        .flat_map(|user| user.connections)
+        .find(|connection| {
+            connection.platform.eq_ignore_ascii_case(platform) && connection.username.eq_ignore_ascii_case(login)
+        })
+        .map(|connection| connection.id))
+}